@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::body::Bytes;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+
+/// What a cached `GET /` body depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub namespace: Option<String>,
+    /// query time, already rounded to the cache granularity
+    pub time: DateTime<Utc>,
+    pub include_types: bool,
+    pub include_admin: bool,
+}
+
+/// Serialized `GET /` bodies of recent queries, so identical queries arriving
+/// together are evaluated once; cleared whenever served state changes, on
+/// `/reload`, `/reload/specs` and `PUT /vars/{name}`
+pub struct ResponseCache {
+    ttl: Duration,
+    capacity: usize,
+    /// query times are rounded down to a multiple of this, so near-simultaneous
+    /// requests share an entry
+    granularity: TimeDelta,
+    entries: Mutex<HashMap<CacheKey, (Instant, Bytes)>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, capacity: usize, granularity: TimeDelta) -> Self {
+        Self {
+            ttl,
+            capacity,
+            granularity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn round(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        time.duration_trunc(self.granularity).unwrap_or(time)
+    }
+
+    /// The cached body for `key`, unless it has expired
+    pub fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(stored, _)| stored.elapsed() < self.ttl)
+            .map(|(_, body)| body.clone())
+    }
+
+    /// Store `body`; when full, expired entries go first, then the oldest
+    pub fn insert(&self, key: CacheKey, body: Bytes) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (stored, _))| *stored)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest.filter(|_| entries.len() >= self.capacity) {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), body));
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Command;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::lunaluz_deserialization::FileFormat;
+
+/// `--config` file: the schedule `filename` plus server options keyed like
+/// the CLI's fields, e.g. `port = 8080` or `cache_ttl = "PT5S"`
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    pub filename: Option<PathBuf>,
+    #[serde(flatten)]
+    pub options: BTreeMap<String, Value>,
+}
+
+impl ServerConfig {
+    /// Read a config file, in TOML, YAML or JSON by its extension
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let raw = FileFormat::from_path(path).parse(&text)?;
+        serde_json::from_value(raw).map_err(|e| e.to_string())
+    }
+
+    /// The options as command line arguments of `command`, so they go through
+    /// the same parsers and checks as flags; placed before the real
+    /// arguments, explicit flags override them
+    pub fn to_args(&self, command: &Command) -> Result<Vec<OsString>, String> {
+        let mut args = Vec::new();
+        for (key, value) in &self.options {
+            let flag = command
+                .get_arguments()
+                .filter(|arg| arg.get_id() != "config")
+                .find(|arg| arg.get_id() == key.as_str())
+                .and_then(|arg| arg.get_long().map(|long| (arg, format!("--{long}="))));
+            let Some((arg, flag)) = flag else {
+                return Err(format!("unknown option '{key}'"));
+            };
+
+            if !arg.get_action().takes_values() {
+                match value {
+                    Value::Bool(true) => args.push(flag.trim_end_matches('=').into()),
+                    Value::Bool(false) => {}
+                    _ => return Err(format!("option '{key}' must be true or false")),
+                }
+                continue;
+            }
+            let values = match value {
+                Value::Array(values) => values.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            for value in values {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => return Err(format!("option '{key}' must be a string or number")),
+                };
+                args.push(format!("{flag}{value}").into());
+            }
+        }
+        Ok(args)
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::schedules::ScheduleMap;
+
+/// A variable present in both files whose resolved schedule differs
+#[derive(Debug, Serialize)]
+pub struct ChangedVariable {
+    pub name: String,
+    /// exported fields that differ, e.g. "period" or "values"; just "kind"
+    /// when the schedule type changed
+    pub fields: Vec<String>,
+}
+
+/// Variables added, removed and changed between two compiled schedule files
+#[derive(Debug, Default, Serialize)]
+pub struct ScheduleDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedVariable>,
+}
+
+impl ScheduleDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// One line per variable: `+` added, `-` removed, `~` changed
+    pub fn lines(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec!["no differences".to_string()];
+        }
+        let added = self.added.iter().map(|name| format!("+ {name}"));
+        let removed = self.removed.iter().map(|name| format!("- {name}"));
+        let changed = self
+            .changed
+            .iter()
+            .map(|change| format!("~ {}: {}", change.name, change.fields.join(", ")));
+        added.chain(removed).chain(changed).collect()
+    }
+}
+
+/// Compare the resolved schedules of two files through their `/export`
+/// serialization, so differences show up as they would be served
+pub fn diff(old: &ScheduleMap, new: &ScheduleMap) -> ScheduleDiff {
+    let mut result = ScheduleDiff::default();
+    for (name, schedule) in new {
+        let Some(previous) = old.get(name) else {
+            result.added.push(name.clone());
+            continue;
+        };
+        let fields = changed_fields(
+            serde_json::to_value(previous).unwrap_or_default(),
+            serde_json::to_value(schedule).unwrap_or_default(),
+        );
+        if !fields.is_empty() {
+            let name = name.clone();
+            result.changed.push(ChangedVariable { name, fields });
+        }
+    }
+    result.removed = old
+        .keys()
+        .filter(|name| !new.contains_key(*name))
+        .cloned()
+        .collect();
+
+    result.added.sort();
+    result.removed.sort();
+    result.changed.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
+fn changed_fields(old: Value, new: Value) -> Vec<String> {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return Vec::new();
+    };
+    if old.get("kind") != new.get("kind") {
+        return vec!["kind".to_string()];
+    }
+    let mut fields: Vec<String> = old
+        .keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedules::{
+        parse_datetime_iso8601, ConstantSchedule, Period, PeriodicSchedule, Schedule,
+    };
+    use chrono::TimeDelta;
+
+    fn led(hours: i64) -> Schedule {
+        let schedule = PeriodicSchedule::new(
+            "intensity".to_string(),
+            parse_datetime_iso8601("2024-01-01T00:00:00Z").unwrap(),
+            Period::Fixed(TimeDelta::hours(hours)),
+            vec![0.0, 6.0],
+            vec![Value::from(100), Value::from(0)],
+            Value::from(0),
+            None,
+        )
+        .unwrap();
+        Schedule::Periodic(schedule)
+    }
+
+    fn lamp() -> Schedule {
+        Schedule::Constant(ConstantSchedule::new(
+            "intensity".to_string(),
+            Value::from(5),
+        ))
+    }
+
+    #[test]
+    fn added_removed_and_changed_variables() {
+        let old = ScheduleMap::from([("led".to_string(), led(24)), ("lamp".to_string(), lamp())]);
+        let new = ScheduleMap::from([("led".to_string(), led(12)), ("fan".to_string(), lamp())]);
+        let diff = diff(&old, &new);
+        assert_eq!(diff.added, ["fan"]);
+        assert_eq!(diff.removed, ["lamp"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].name, "led");
+        assert_eq!(diff.changed[0].fields, ["period"]);
+        assert_eq!(diff.lines(), ["+ fan", "- lamp", "~ led: period"]);
+    }
+
+    #[test]
+    fn a_changed_kind_is_reported_alone() {
+        let old = ScheduleMap::from([("led".to_string(), led(24))]);
+        let new = ScheduleMap::from([("led".to_string(), lamp())]);
+        assert_eq!(diff(&old, &new).changed[0].fields, ["kind"]);
+        assert_eq!(diff(&old, &old).lines(), ["no differences"]);
+    }
+}
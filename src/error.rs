@@ -0,0 +1,135 @@
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors from parsing times and durations and from building schedules
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("Error parsing time: {0}")]
+    InvalidDateTime(String),
+    #[error("Invalid time duration: {0}")]
+    InvalidDuration(String),
+    #[error("Duration contains unsupported units (e.g. months, years)")]
+    UnsupportedDurationUnit,
+    #[error("Duration out of range")]
+    DurationOutOfRange,
+    #[error("Failed to convert std Duration to TimeDelta: {0}")]
+    DurationConversion(#[from] chrono::OutOfRangeError),
+    #[error(transparent)]
+    FloatDuration(#[from] std::time::TryFromFloatSecsError),
+    #[error("Calendar period '{0}' must be whole months or years only")]
+    MixedCalendarPeriod(String),
+    #[error("Epoch timestamp out of range: {0}")]
+    EpochOutOfRange(f64),
+    #[error("Invalid output timezone '{input}': {message}")]
+    InvalidTimezone { input: String, message: String },
+    #[error("Invalid input timezone '{input}': {message}")]
+    InvalidInputTimezone { input: String, message: String },
+    #[error("Local time {0} does not exist in the input timezone")]
+    NonexistentLocalTime(String),
+    #[error("Unknown timezone '{name}': {message}")]
+    UnknownTimezone { name: String, message: String },
+    #[error("Timezone offset out of range")]
+    TimezoneOutOfRange,
+    #[error("Invalid site coordinates: ({latitude}, {longitude})")]
+    InvalidCoordinates { latitude: f64, longitude: f64 },
+    #[error("Invalid start date format: {0}")]
+    InvalidStartDate(#[source] Box<ScheduleError>),
+
+    /// a schedule entry that failed validation
+    #[error("Invalid schedule '{name}': {reason}")]
+    InvalidEntry { name: String, reason: String },
+    #[error("Unknown variable type for {name}")]
+    UnknownVariableType { name: String, var_type: String },
+    #[error("Schedule '{name}' requires {field}")]
+    MissingField { name: String, field: String },
+    #[error("expected {expected}, found {value}")]
+    ValueTypeMismatch { value: Value, expected: String },
+    /// a field of schedule `name` that didn't parse
+    #[error("Failed to parse {field} for '{name}': {error}")]
+    InvalidField {
+        name: String,
+        field: &'static str,
+        #[source]
+        error: Box<ScheduleError>,
+    },
+    #[error("Invalid periodic schedule '{name}': {error}")]
+    InvalidPeriodic {
+        name: String,
+        #[source]
+        error: Box<ScheduleError>,
+    },
+    #[error("Failed to parse time(s) for periodic schedule: {0}")]
+    InvalidTimes(#[source] Box<ScheduleError>),
+    #[error("Period of periodic schedule must be positive")]
+    NonPositivePeriod,
+    #[error("Periodic schedule must have at least one time")]
+    EmptyTimes,
+    #[error("Periodic schedule has {times} times but {values} values")]
+    LengthMismatch { times: usize, values: usize },
+    #[error("Times of periodic schedule must be strictly increasing (index {index})")]
+    UnsortedTimes { index: usize },
+    #[error("time {time} is not within every cycle of {period}")]
+    TimeOutsideCycle { time: String, period: String },
+    #[error("EndDate of periodic schedule must be after its start")]
+    EndBeforeStart,
+    #[error("Periodic schedule has more than one point at {time}")]
+    DuplicateTime { time: String },
+    #[error("PeriodicScheduleBuilder requires {0}")]
+    BuilderMissing(&'static str),
+    #[error("Linear interpolation of '{name}' requires an Interval or Ratio variable type")]
+    InterpolationType { name: String },
+    #[error("Invalid value for '{name}': {error}")]
+    InvalidValue {
+        name: String,
+        #[source]
+        error: Box<ScheduleError>,
+    },
+    #[error("Invalid breakpoint {index} of '{name}': {error}")]
+    InvalidBreakpoint {
+        name: String,
+        index: usize,
+        #[source]
+        error: Box<ScheduleError>,
+    },
+    #[error("Breakpoints of '{name}' must be in increasing time order (index {index})")]
+    BreakpointOrder { name: String, index: usize },
+    #[error("Invalid event {index} of '{name}': {error}")]
+    InvalidEvent {
+        name: String,
+        index: usize,
+        #[source]
+        error: Box<ScheduleError>,
+    },
+    #[error("Events of '{name}' must be in time order (index {index})")]
+    EventOrder { name: String, index: usize },
+    #[error("Duration must be positive")]
+    NonPositiveDuration,
+    #[error("Variable '{name}' is defined in both EventSchedules and VariableSchedules")]
+    DuplicateVariable { name: String },
+    #[error("Invalid formula for '{name}': {message}")]
+    InvalidFormula { name: String, message: String },
+    #[error("Variable '{target}' followed by '{name}' must be periodic")]
+    FollowNotPeriodic { name: String, target: String },
+    /// `relation` is how `name` uses the variable, e.g. "referenced by"
+    #[error("Unknown variable '{target}' {relation} '{name}'")]
+    UnknownVariable {
+        name: String,
+        target: String,
+        relation: &'static str,
+    },
+    #[error("Variable '{target}' referenced by '{name}' is not numeric")]
+    NotNumeric { name: String, target: String },
+    #[error("Cyclic variable reference: {}", .0.join(" -> "))]
+    CyclicReference(Vec<String>),
+}
+
+impl ScheduleError {
+    /// attach the field and schedule name this error came from
+    pub fn in_field(self, name: &str, field: &'static str) -> Self {
+        ScheduleError::InvalidField {
+            name: name.to_owned(),
+            field,
+            error: Box::new(self),
+        }
+    }
+}
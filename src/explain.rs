@@ -0,0 +1,213 @@
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+
+use crate::{
+    lunaluz_deserialization::{
+        InterpolationMode, LunaLuz, PeriodicEntry, PreStartBehavior, ScheduleEntry,
+    },
+    schedules::{PeriodicSchedule, Schedule, ScheduleMap, ValueTransform, VarSchedule},
+};
+
+fn fmt_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn fmt_hours(delta: TimeDelta) -> String {
+    format!("{}h", delta.num_milliseconds() as f64 / 3.6e6)
+}
+
+/// Step-by-step trace of how `var` gets its value at `time`
+pub fn explain(
+    file: &LunaLuz,
+    schedules: &ScheduleMap,
+    namespace: Option<&str>,
+    var: &str,
+    time: DateTime<Utc>,
+) -> Result<Vec<String>, String> {
+    let schedule = schedules
+        .get(var)
+        .ok_or_else(|| format!("Unknown variable: '{var}'"))?;
+    // namespaces loaded from their own files have no entries in `file`
+    let entries = match namespace {
+        Some(id) => file.extensions.get(id).map(|ext| &ext.variable_schedules),
+        None => Some(&file.variable_schedules),
+    };
+
+    let mut lines = vec![
+        format!("variable: {var} ({})", schedule.var_type()),
+        format!("time: {}", fmt_time(&time)),
+    ];
+
+    match schedule.raw() {
+        Schedule::Constant(_) => lines.push("constant schedule".to_string()),
+        Schedule::Periodic(periodic) => {
+            if let Some(ScheduleEntry::Periodic(entry)) = entries.and_then(|e| e.get(var)) {
+                lines.push(start_point_origin(file, entry));
+            }
+            explain_periodic(periodic, &time, &mut lines);
+        }
+        Schedule::Follow(follow) => {
+            let delayed = time - follow.delay;
+            lines.push(format!(
+                "follows a periodic source delayed by {}; source time: {}",
+                fmt_hours(follow.delay),
+                fmt_time(&delayed)
+            ));
+            explain_periodic(&follow.source, &delayed, &mut lines);
+            if follow.map.is_some() {
+                lines.push("source value passed through Map".to_string());
+            }
+        }
+        Schedule::Derived(derived) => {
+            for (name, input) in &derived.inputs {
+                lines.push(format!("input {name} = {}", input.floor_search(&time)));
+            }
+        }
+        Schedule::Absolute(absolute) => {
+            let index = absolute.breakpoints.partition_point(|(t, _)| *t <= time);
+            match index.checked_sub(1) {
+                Some(i) => lines.push(format!(
+                    "breakpoint {i} at {} in effect",
+                    fmt_time(&absolute.breakpoints[i].0)
+                )),
+                None => lines.push("before the first breakpoint; default used".to_string()),
+            }
+        }
+        Schedule::Event(event) => {
+            let started = event.events.partition_point(|(start, _, _)| *start <= time);
+            let active = event.events[..started]
+                .iter()
+                .rposition(|(_, end, _)| *end > time);
+            match active {
+                Some(i) => lines.push(format!(
+                    "event {i} from {} to {} in effect",
+                    fmt_time(&event.events[i].0),
+                    fmt_time(&event.events[i].1)
+                )),
+                None => lines.push("no event in effect; default used".to_string()),
+            }
+        }
+        Schedule::Ramp(ramp) => {
+            let index = ramp.anchors.partition_point(|(t, _)| *t <= time);
+            match (index.checked_sub(1), ramp.anchors.get(index)) {
+                (Some(i), Some((next, _))) => lines.push(format!(
+                    "interpolated between anchor {i} at {} and anchor {index} at {}",
+                    fmt_time(&ramp.anchors[i].0),
+                    fmt_time(next)
+                )),
+                (Some(i), None) => lines.push(format!("after the last anchor; anchor {i} held")),
+                (None, _) => lines.push("before the first anchor; anchor 0 held".to_string()),
+            }
+        }
+        Schedule::Transformed(_) => unreachable!("raw schedules are never transformed"),
+    }
+
+    if let Schedule::Transformed(transformed) = schedule {
+        let ValueTransform { scale, offset } = transformed.transform;
+        lines.push(format!(
+            "numeric value scaled by {scale} then offset by {offset}"
+        ));
+    }
+
+    lines.push(format!("value: {}", schedule.floor_search(&time)));
+    Ok(lines)
+}
+
+/// How the entry's start point was anchored
+fn start_point_origin(file: &LunaLuz, entry: &PeriodicEntry) -> String {
+    if entry.period.is_t24() {
+        format!(
+            "start_point: T24 anchor, local midnight ({}) of StartDate {} plus StartOffset {}",
+            file.info.timezone, file.info.start_date, file.info.start_offset
+        )
+    } else if let Some(offset_time) = &entry.offset_time {
+        format!(
+            "start_point: StartDate {} plus OffsetTime {offset_time}",
+            file.info.start_date
+        )
+    } else {
+        format!("start_point: StartDate {}", file.info.start_date)
+    }
+}
+
+fn explain_periodic(schedule: &PeriodicSchedule, time: &DateTime<Utc>, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "start_point = {}, period = {}",
+        fmt_time(&schedule.start_point),
+        schedule.period
+    ));
+
+    let pre_start = match schedule.pre_start {
+        PreStartBehavior::Default => "default used",
+        PreStartBehavior::HoldLast => "last value held (PreStart hold_last)",
+    };
+    if *time < schedule.start_point {
+        lines.push(format!("time is before start_point; {pre_start}"));
+        return;
+    }
+    if let Some(end) = schedule.end_point.filter(|end| time >= end) {
+        lines.push(format!(
+            "time is at or after end_point {}; default used",
+            fmt_time(&end)
+        ));
+        return;
+    }
+
+    if let Some(solar) = &schedule.solar {
+        match solar.event_on(time) {
+            Some(event) => lines.push(format!(
+                "solar: times are offsets from {:?} on the local day, at {}; schedule_time = {}",
+                solar.event,
+                fmt_time(&event),
+                fmt_hours(*time - event)
+            )),
+            None => lines.push(format!("solar: no {:?} on the local day", solar.event)),
+        }
+    } else if let Some(most_recent_start) = schedule.most_recent_start(time) {
+        lines.push(format!(
+            "most_recent_start = {}",
+            fmt_time(&most_recent_start)
+        ));
+        let schedule_time = *time - most_recent_start;
+        lines.push(format!("schedule_time = {}", fmt_hours(schedule_time)));
+    }
+
+    let times: Vec<String> = schedule.times.iter().map(|t| fmt_hours(*t)).collect();
+    lines.push(format!("times = [{}]", times.join(", ")));
+
+    if schedule.interpolation == InterpolationMode::Linear {
+        let stale = schedule.floor_point(time).and_then(|(_, since)| {
+            let max = schedule.max_stale?;
+            (*time - since > max).then_some((*time - since, max))
+        });
+        match stale {
+            Some((held, max)) => lines.push(format!(
+                "previous point was {} ago, longer than MaxStale {}; default used",
+                fmt_hours(held),
+                fmt_hours(max)
+            )),
+            None => lines.push("values ramp linearly between the surrounding points".to_string()),
+        }
+        return;
+    }
+
+    let Some((index, since)) = schedule.floor_point(time) else {
+        lines.push(format!(
+            "schedule_time is before the first point; {pre_start}"
+        ));
+        return;
+    };
+    lines.push(format!(
+        "binary search landed on index {index} (times[{index}] = {}), in effect since {}",
+        fmt_hours(schedule.times[index]),
+        fmt_time(&since)
+    ));
+
+    match schedule.max_stale {
+        Some(max) if *time - since > max => lines.push(format!(
+            "held for {}, longer than MaxStale {}; default used",
+            fmt_hours(*time - since),
+            fmt_hours(max)
+        )),
+        _ => lines.push(format!("values[{index}] used")),
+    }
+}
@@ -0,0 +1,64 @@
+//! Parse LunaLuz schedule files and evaluate their variables in-process; the
+//! `luz_optimon` binary serves the same schedules over HTTP
+//!
+//! ```
+//! use luz_optimon::{
+//!     parse_datetime_iso8601, parse_schedule_file, parse_schedules, FileFormat, VarSchedule,
+//! };
+//!
+//! let file = r#"{
+//!     "Info": {
+//!         "Version": "1.0", "Timezone": 0, "StartDate": "2024-01-01T00:00:00Z",
+//!         "StartOffset": "PT0H", "ExperimentName": "Demo", "CabinetID": "C1",
+//!         "User": "tester", "Description": "sample",
+//!         "Parents": {"Primary": "none", "Secondary": []}
+//!     },
+//!     "EventSchedules": {},
+//!     "VarTypeSpecs": {
+//!         "intensity": {"VariableType": "Ratio", "DefaultValue": 0, "Description": "light"}
+//!     },
+//!     "VariableSchedules": {
+//!         "led": {"VariableType": "intensity", "Period": 24, "Times": [6, 18], "Values": [100, 0]}
+//!     }
+//! }"#;
+//!
+//! let file = parse_schedule_file(file, FileFormat::Json, false).unwrap();
+//! let (schedules, _namespaces) = parse_schedules(file).unwrap();
+//! let time = parse_datetime_iso8601("2024-01-02T12:00:00Z").unwrap();
+//! assert_eq!(schedules["led"].floor_search(&time), 100);
+//! ```
+
+pub mod error;
+pub mod lunaluz_deserialization;
+pub mod schedules;
+
+// the server behind the binary; not a stable API
+#[doc(hidden)]
+pub mod cache;
+#[doc(hidden)]
+pub mod clock;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod explain;
+#[doc(hidden)]
+pub mod metrics;
+#[cfg(feature = "openapi")]
+#[doc(hidden)]
+pub mod openapi;
+#[doc(hidden)]
+pub mod rate_limit;
+#[doc(hidden)]
+pub mod server_actions;
+
+pub use error::ScheduleError;
+pub use lunaluz_deserialization::{
+    parse_schedule_file, read_schedule_file, FileFormat, LunaLuz, ScheduleEntry, ScheduleInfo,
+    VariableTypeSpec,
+};
+pub use schedules::{
+    parse_datetime_in, parse_datetime_iso8601, parse_schedules, NamespaceMap, Period,
+    PeriodicSchedule, PeriodicScheduleBuilder, Schedule, ScheduleMap, VarSchedule, Zone,
+};
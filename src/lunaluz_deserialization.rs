@@ -1,12 +1,13 @@
 #![allow(dead_code)]
 
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::{collections::HashMap, io, path::Path};
 
 // ------------------------- Variable Type Spec -------------------------
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "PascalCase")]
 pub enum VarDataType {
     Interval,
@@ -17,6 +18,7 @@ pub enum VarDataType {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct VariableTypeSpec {
     #[serde(rename = "VariableType")]
     pub var_type: VarDataType,
@@ -29,69 +31,466 @@ pub struct VariableTypeSpec {
 
     #[serde(rename = "Categories", default)]
     pub categories: Option<Vec<String>>,
+
+    /// unit of the values, e.g. "lux" or "°C"
+    #[serde(rename = "Unit", default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+
+    /// inclusive bounds on the values of Interval and Ratio types
+    #[serde(rename = "Min", default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(rename = "Max", default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
 }
 
 // ------------------------- Schedule Section -------------------------
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub struct ScheduleHeader {
     pub variable_type: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub schedule_type: Option<ScheduleType>,
+    /// numeric values are served as `value * Scale + Offset`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ScheduleType {
     Constant,
     Periodic,
+    Derived,
+    Follow,
+    Absolute,
+    Ramp,
     Default,
 }
 
-/// intermediate representation of variable schedule entries
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConstantEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    #[serde(rename = "Value")]
+    pub value: JsonValue,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PeriodicEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    #[serde(rename = "Period")]
+    pub period: PeriodSpec,
+    #[serde(rename = "Times")]
+    pub times: Vec<f64>,
+    #[serde(rename = "Values")]
+    pub values: Vec<JsonValue>,
+    #[serde(
+        rename = "OffsetTime",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub offset_time: Option<OffsetSpec>,
+    /// ISO 8601 duration after which a held value reverts to default
+    #[serde(rename = "MaxStale", default, skip_serializing_if = "Option::is_none")]
+    pub max_stale: Option<String>,
+    /// direction the (numeric) values must follow across a cycle
+    #[serde(rename = "Monotonic", default, skip_serializing_if = "Option::is_none")]
+    pub monotonic: Option<Monotonic>,
+    /// T24 only: `Times` are offsets from each local day's sunrise or sunset
+    #[serde(rename = "Solar", default, skip_serializing_if = "Option::is_none")]
+    pub solar: Option<Solar>,
+    /// hold the previous period's last value until the first of `Times`
+    #[serde(rename = "CarryIn", default, skip_serializing_if = "is_default")]
+    pub carry_in: bool,
+    /// ISO 8601 time from which the schedule serves its default
+    #[serde(rename = "EndDate", default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    /// how values between points are found (default: hold the previous point)
+    #[serde(rename = "Interpolation", default, skip_serializing_if = "is_default")]
+    pub interpolation: InterpolationMode,
+    /// what is served before the start and before a period's first point
+    #[serde(rename = "PreStart", default, skip_serializing_if = "is_default")]
+    pub pre_start: PreStartBehavior,
+}
+
+impl PeriodicEntry {
+    /// Warning for a fixed-length `Period` written as an ISO 8601 duration next
+    /// to `Times`, which are always hours; the mix is valid but easy to
+    /// misread. Calendar periods (months, years) have no hours form
+    pub fn mixed_styles(&self) -> Option<String> {
+        match &self.period {
+            PeriodSpec::Duration(period) if !self.times.is_empty() => {
+                let date_part = period.split('T').next().unwrap_or_default();
+                (!date_part.contains(['Y', 'M'])).then(|| {
+                    format!("Period {period} is an ISO 8601 duration but Times are in hours")
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InterpolationMode {
+    /// hold each point's value until the next (a staircase)
+    #[default]
+    Floor,
+    /// ramp numeric values linearly from each point to the next
+    Linear,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PreStartBehavior {
+    /// serve the type's default value
+    #[default]
+    Default,
+    /// hold the last of `Values`, as if the cycle had already been running
+    HoldLast,
+}
+
+/// cycle length in hours, or as an ISO 8601 duration (e.g. "P1M"); months and
+/// years follow the calendar
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PeriodSpec {
+    Hours(f64),
+    Duration(String),
+}
+
+impl PeriodSpec {
+    pub fn hours(&self) -> Option<f64> {
+        match self {
+            PeriodSpec::Hours(hours) => Some(*hours),
+            PeriodSpec::Duration(_) => None,
+        }
+    }
+
+    /// a 24 hour period, whose start is anchored to local midnight
+    pub fn is_t24(&self) -> bool {
+        self.hours() == Some(24.0)
+    }
+}
+
+/// start point delay in hours, or as an ISO 8601 duration (e.g. "PT90M") like
+/// the Info StartOffset
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OffsetSpec {
+    Hours(f64),
+    Duration(String),
+}
+
+impl std::fmt::Display for OffsetSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OffsetSpec::Hours(hours) => write!(f, "{hours}h"),
+            OffsetSpec::Duration(duration) => f.write_str(duration),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Solar {
+    Sunrise,
+    Sunset,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Monotonic {
+    Increasing,
+    Decreasing,
+}
+
+/// computed at query time from other variables in the same namespace
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DerivedEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    #[serde(rename = "Formula")]
+    pub formula: String,
+}
+
+/// follows a periodic variable's transitions after a fixed delay
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FollowEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    #[serde(rename = "Source")]
+    pub source: String,
+    /// ISO 8601 duration
+    #[serde(rename = "Delay")]
+    pub delay: String,
+    /// source value (strings as-is, others as JSON text) to output value;
+    /// unmapped values give the default. Values pass through if omitted
+    #[serde(rename = "Map", default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<HashMap<String, JsonValue>>,
+}
+
+/// holds each breakpoint's value until the next one; never repeats
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AbsoluteEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    #[serde(rename = "Breakpoints")]
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// ISO 8601 time the value takes effect
+    #[serde(rename = "Time")]
+    pub time: String,
+    #[serde(rename = "Value")]
+    pub value: JsonValue,
+}
+
+// ------------------------- Event Schedules -------------------------
+
+/// one-off events, each holding its value for a duration before the
+/// variable reverts to its default
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventEntry {
+    #[serde(rename = "VariableType")]
+    pub variable_type: String,
+    /// sorted by time; where events overlap the later one wins
+    #[serde(rename = "Events")]
+    pub events: Vec<EventPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventPoint {
+    /// ISO 8601 time the event starts
+    #[serde(rename = "Time")]
+    pub time: String,
+    #[serde(rename = "Value")]
+    pub value: JsonValue,
+    /// ISO 8601 duration the value holds for
+    #[serde(rename = "Duration")]
+    pub duration: String,
+}
+
+impl EventEntry {
+    /// Fields accepted for an event schedule and for each of its events; keep
+    /// in sync with the structs
+    const FIELDS: &'static [&'static str] = &["VariableType", "Events"];
+    const POINT_FIELDS: &'static [&'static str] = &["Time", "Value", "Duration"];
+
+    /// Reject fields an event schedule or its events don't use
+    /// (`--strict-fields`); by default they are silently ignored
+    pub fn check_fields(raw: &JsonValue) -> Result<(), String> {
+        let JsonValue::Object(fields) = raw else {
+            return Ok(());
+        };
+        check_keys(fields, EventEntry::FIELDS, "event schedule")?;
+        let events = fields.get("Events").and_then(JsonValue::as_array);
+        for (i, event) in events.into_iter().flatten().enumerate() {
+            if let JsonValue::Object(fields) = event {
+                check_keys(fields, EventEntry::POINT_FIELDS, &format!("event {i}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error naming the first key of `fields` not in `known`
+fn check_keys(
+    fields: &serde_json::Map<String, JsonValue>,
+    known: &[&str],
+    what: &str,
+) -> Result<(), String> {
+    match fields.keys().find(|key| !known.contains(&key.as_str())) {
+        Some(key) => Err(format!(
+            "unknown field `{key}` for {what}, expected one of {known:?}"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// ramps linearly between absolute anchor points; never repeats
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RampEntry {
+    #[serde(flatten)]
+    pub header: ScheduleHeader,
+    /// ISO 8601 anchor times, in increasing order
+    #[serde(rename = "Times")]
+    pub times: Vec<String>,
+    #[serde(rename = "Values")]
+    pub values: Vec<f64>,
+}
+
+/// intermediate representation of variable schedule entries; serialized
+/// as the entry's own fields, its type left to be inferred from them
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum ScheduleEntry {
-    Constant {
-        #[serde(flatten)]
-        header: ScheduleHeader,
-        #[serde(rename = "Value")]
-        value: JsonValue,
-    },
-    Periodic {
-        #[serde(flatten)]
-        header: ScheduleHeader,
-        #[serde(rename = "Period")]
-        period: f64,
-        #[serde(rename = "Times")]
-        times: Vec<f64>,
-        #[serde(rename = "Values")]
-        values: Vec<JsonValue>,
-        #[serde(rename = "OffsetTime", default)]
-        offset_time: Option<f64>,
-    },
-    Default {
-        #[serde(flatten)]
-        header: ScheduleHeader,
-    },
+    Constant(ConstantEntry),
+    Periodic(PeriodicEntry),
+    Derived(DerivedEntry),
+    Follow(FollowEntry),
+    Absolute(AbsoluteEntry),
+    Ramp(RampEntry),
+    Default(ScheduleHeader),
+}
+
+impl<'de> Deserialize<'de> for ScheduleEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = JsonValue::deserialize(deserializer)?;
+        ScheduleEntry::from_json(raw).map_err(D::Error::custom)
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Deserialize a map of schedule entries, naming the variable on failure
+fn deserialize_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, ScheduleEntry>, D::Error> {
+    let raw = HashMap::<String, JsonValue>::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(name, value)| {
+            ScheduleEntry::from_json(value)
+                .map(|entry| (name.clone(), entry))
+                .map_err(|e| D::Error::custom(format!("Invalid schedule for '{name}': {e}")))
+        })
+        .collect()
 }
 
 impl ScheduleEntry {
+    /// Two-stage parse: the schedule type is taken from `ScheduleType` or
+    /// inferred from which fields are present, then the entry is parsed as
+    /// that type so errors can point at the missing or mistyped field
+    pub fn from_json(raw: JsonValue) -> Result<Self, String> {
+        let header: ScheduleHeader =
+            serde_json::from_value(raw.clone()).map_err(|e| e.to_string())?;
+        let JsonValue::Object(fields) = &raw else {
+            return Err("expected an object".to_string());
+        };
+
+        let inferred = infer_schedule_type(fields);
+        let schedule_type = match header.schedule_type {
+            Some(specified) if specified != inferred => {
+                return Err(type_mismatch(specified, inferred));
+            }
+            Some(specified) => specified,
+            // an entry with no shape fields is likely a typo; defaults are declared
+            None if inferred == ScheduleType::Default => return Err(missing_shape(fields)),
+            None => inferred,
+        };
+
+        let entry = match schedule_type {
+            ScheduleType::Constant => serde_json::from_value(raw).map(ScheduleEntry::Constant),
+            ScheduleType::Periodic => serde_json::from_value(raw).map(ScheduleEntry::Periodic),
+            ScheduleType::Derived => serde_json::from_value(raw).map(ScheduleEntry::Derived),
+            ScheduleType::Follow => serde_json::from_value(raw).map(ScheduleEntry::Follow),
+            ScheduleType::Absolute => serde_json::from_value(raw).map(ScheduleEntry::Absolute),
+            ScheduleType::Ramp => serde_json::from_value(raw).map(ScheduleEntry::Ramp),
+            ScheduleType::Default => Ok(ScheduleEntry::Default(header)),
+        };
+        entry.map_err(|e| format!("{e} ({schedule_type:?} schedule)"))
+    }
+
+    /// Fields accepted for a schedule type; keep in sync with the entry structs,
+    /// which `known_fields_cover_every_entry_field` checks
+    fn known_fields(schedule_type: ScheduleType) -> &'static [&'static str] {
+        match schedule_type {
+            ScheduleType::Constant => &["Value"],
+            ScheduleType::Periodic => &[
+                "Period",
+                "Times",
+                "Values",
+                "OffsetTime",
+                "MaxStale",
+                "Monotonic",
+                "Solar",
+                "CarryIn",
+                "EndDate",
+                "Interpolation",
+                "PreStart",
+            ],
+            ScheduleType::Derived => &["Formula"],
+            ScheduleType::Follow => &["Source", "Delay", "Map"],
+            ScheduleType::Absolute => &["Breakpoints"],
+            ScheduleType::Ramp => &["Times", "Values"],
+            ScheduleType::Default => &[],
+        }
+    }
+
+    /// Reject fields the entry's schedule type doesn't use (`--strict-fields`);
+    /// by default they are silently ignored
+    pub fn check_fields(raw: &JsonValue) -> Result<(), String> {
+        let JsonValue::Object(fields) = raw else {
+            return Ok(());
+        };
+        let schedule_type = fields
+            .get("ScheduleType")
+            .and_then(|t| ScheduleType::deserialize(t).ok())
+            .unwrap_or_else(|| infer_schedule_type(fields));
+
+        let known = ScheduleEntry::known_fields(schedule_type);
+        for key in fields.keys() {
+            let header_field = HEADER_FIELDS.contains(&key.as_str());
+            if !header_field && !known.contains(&key.as_str()) {
+                return Err(format!(
+                    "unknown field `{key}` for {schedule_type:?} schedule, expected one of {known:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn header(&self) -> &ScheduleHeader {
         match self {
-            ScheduleEntry::Constant { header, .. } => &header,
-            ScheduleEntry::Periodic { header, .. } => &header,
-            ScheduleEntry::Default { header } => &header,
+            ScheduleEntry::Constant(entry) => &entry.header,
+            ScheduleEntry::Periodic(entry) => &entry.header,
+            ScheduleEntry::Derived(entry) => &entry.header,
+            ScheduleEntry::Follow(entry) => &entry.header,
+            ScheduleEntry::Absolute(entry) => &entry.header,
+            ScheduleEntry::Ramp(entry) => &entry.header,
+            ScheduleEntry::Default(header) => header,
+        }
+    }
+
+    pub(crate) fn header_mut(&mut self) -> &mut ScheduleHeader {
+        match self {
+            ScheduleEntry::Constant(entry) => &mut entry.header,
+            ScheduleEntry::Periodic(entry) => &mut entry.header,
+            ScheduleEntry::Derived(entry) => &mut entry.header,
+            ScheduleEntry::Follow(entry) => &mut entry.header,
+            ScheduleEntry::Absolute(entry) => &mut entry.header,
+            ScheduleEntry::Ramp(entry) => &mut entry.header,
+            ScheduleEntry::Default(header) => header,
         }
     }
 
     fn schedule_type(&self) -> ScheduleType {
         match self {
-            ScheduleEntry::Constant { .. } => ScheduleType::Constant,
-            ScheduleEntry::Periodic { .. } => ScheduleType::Periodic,
-            ScheduleEntry::Default { .. } => ScheduleType::Default,
+            ScheduleEntry::Constant(_) => ScheduleType::Constant,
+            ScheduleEntry::Periodic(_) => ScheduleType::Periodic,
+            ScheduleEntry::Derived(_) => ScheduleType::Derived,
+            ScheduleEntry::Follow(_) => ScheduleType::Follow,
+            ScheduleEntry::Absolute(_) => ScheduleType::Absolute,
+            ScheduleEntry::Ramp(_) => ScheduleType::Ramp,
+            ScheduleEntry::Default(_) => ScheduleType::Default,
+        }
+    }
+
+    /// The entry's Scale/Offset as `(scale, offset)`, if either is set
+    pub fn transform(&self) -> Option<(f64, f64)> {
+        let header = self.header();
+        if header.scale.is_none() && header.offset.is_none() {
+            return None;
         }
+        Some((header.scale.unwrap_or(1.0), header.offset.unwrap_or(0.0)))
     }
 
     pub fn variable_type(&self) -> &str {
@@ -99,55 +498,227 @@ impl ScheduleEntry {
     }
 
     pub fn is_valid(&self) -> Result<(), String> {
-        let var_type = self.variable_type();
-
-        // if schedule type is specified, validate it with enum variant
+        // parsed entries are checked in `from_json`; this catches built ones
         if let Some(specified) = self.header().schedule_type {
             let inferred = self.schedule_type();
             if inferred != specified {
-                return Err(format!(
-                    "Fields of '{}' do not match specified schedule type ({:?}); {:?} schedule was inferred",
-                    var_type,
-                    specified,
-                    inferred
-                ));
+                return Err(type_mismatch(specified, inferred));
             }
         };
 
         // if schedule type is periodic T24, offset time shouldn't be allowed
         // offset time is intended for easy desync of non-T24 cycles;
         // If T24 cycles need to be desynced, it should be done explicitly
-        if let ScheduleEntry::Periodic { period, offset_time, .. } = self {
-            if *period == 24.0 && offset_time.is_some() {
-                return Err(format!(
-                    "Error parsing {}: T24 Periodic schedules are not allowed to include an offset time",
-                    var_type
-                ))
+        if let ScheduleEntry::Periodic(entry) = self {
+            if entry.period.is_t24() && entry.offset_time.is_some() {
+                return Err(
+                    "T24 Periodic schedules are not allowed to include an offset time".to_string(),
+                );
+            }
+
+            // a point at or past the period end would never be reached;
+            // calendar periods are checked once their shortest cycle is known
+            let period = entry.period.hours().unwrap_or(f64::INFINITY);
+            for (i, &time) in entry.times.iter().enumerate() {
+                if time >= period {
+                    // only where the time is far out, but fits the period read as minutes
+                    let hint = if time >= 2.0 * period && time / 60.0 < period {
+                        "; did you enter minutes instead of hours?"
+                    } else {
+                        ""
+                    };
+                    return Err(format!(
+                        "time {} at index {} is not within the period of {} hours{}",
+                        time, i, period, hint
+                    ));
+                }
+            }
+
+            if entry.solar.is_some() && !entry.period.is_t24() {
+                return Err("Solar schedules must have a period of 24".to_string());
+            }
+
+            if entry.solar.is_some() && entry.interpolation == InterpolationMode::Linear {
+                return Err("Solar schedules cannot use linear interpolation".to_string());
+            }
+
+            if let Some(monotonic) = entry.monotonic {
+                check_monotonic(&entry.values, monotonic)?;
             }
         }
 
         Ok(())
     }
 }
+
+fn type_mismatch(specified: ScheduleType, inferred: ScheduleType) -> String {
+    format!(
+        "Fields do not match specified schedule type ({specified:?}); {inferred:?} schedule was inferred"
+    )
+}
+
+/// Fields of every schedule entry, whatever its type
+const HEADER_FIELDS: [&str; 4] = ["VariableType", "ScheduleType", "Scale", "Offset"];
+
+/// Error for an entry with none of the fields a schedule type is inferred
+/// from, naming the first field it doesn't recognise
+fn missing_shape(fields: &serde_json::Map<String, JsonValue>) -> String {
+    let message = "missing field `Value` (or `Period`/`Times`/`Values`, `Formula`, `Source`, \
+        `Breakpoints`); set \"ScheduleType\": \"default\" to serve the type's default";
+    match fields
+        .keys()
+        .find(|key| !HEADER_FIELDS.contains(&key.as_str()))
+    {
+        Some(key) => format!("{message}; unknown field `{key}`"),
+        None => message.to_string(),
+    }
+}
+
+/// Schedule type implied by which fields an entry has
+fn infer_schedule_type(fields: &serde_json::Map<String, JsonValue>) -> ScheduleType {
+    let has = |key: &str| fields.contains_key(key);
+    // ramps anchor at ISO 8601 times where periodic schedules use hours
+    let absolute_times = fields
+        .get("Times")
+        .and_then(JsonValue::as_array)
+        .is_some_and(|times| times.iter().any(JsonValue::is_string));
+    if has("Value") {
+        ScheduleType::Constant
+    } else if absolute_times && !has("Period") {
+        ScheduleType::Ramp
+    } else if has("Period") || has("Times") || has("Values") {
+        ScheduleType::Periodic
+    } else if has("Formula") {
+        ScheduleType::Derived
+    } else if has("Source") {
+        ScheduleType::Follow
+    } else if has("Breakpoints") {
+        ScheduleType::Absolute
+    } else {
+        ScheduleType::Default
+    }
+}
+
+/// Check every schedule entry and event schedule of a raw schedule file for
+/// unknown fields
+fn check_strict_fields(file: &JsonValue) -> Result<(), String> {
+    let events = file.get("EventSchedules").and_then(JsonValue::as_object);
+    for (name, entry) in events.into_iter().flatten() {
+        EventEntry::check_fields(entry)
+            .map_err(|e| format!("Invalid event schedule for '{name}': {e}"))?;
+    }
+
+    let global = file.get("VariableSchedules");
+    let extensions = file
+        .get("Extensions")
+        .and_then(JsonValue::as_object)
+        .into_iter()
+        .flat_map(|exts| exts.values())
+        .map(|ext| ext.get("VariableSchedules"));
+
+    for schedules in std::iter::once(global).chain(extensions).flatten() {
+        let Some(schedules) = schedules.as_object() else {
+            continue;
+        };
+        for (name, entry) in schedules {
+            ScheduleEntry::check_fields(entry)
+                .map_err(|e| format!("Invalid schedule for '{name}': {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Reject periodic entries mixing Period and Times styles (`--strict-fields`);
+/// otherwise they only log a warning when built
+fn check_strict_styles(file: &LunaLuz) -> Result<(), String> {
+    let extensions = file.extensions.values().map(|ext| &ext.variable_schedules);
+    for schedules in std::iter::once(&file.variable_schedules).chain(extensions) {
+        for (name, entry) in schedules {
+            if let ScheduleEntry::Periodic(entry) = entry {
+                if let Some(warning) = entry.mixed_styles() {
+                    return Err(format!("Invalid schedule for '{name}': {warning}"));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Values may repeat, but never step against the declared direction
+fn check_monotonic(values: &[JsonValue], monotonic: Monotonic) -> Result<(), String> {
+    let numbers = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            v.as_f64()
+                .ok_or_else(|| format!("Monotonic schedule has non-numeric value at index {i}"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    for (i, pair) in numbers.windows(2).enumerate() {
+        let violated = match monotonic {
+            Monotonic::Increasing => pair[1] < pair[0],
+            Monotonic::Decreasing => pair[1] > pair[0],
+        };
+        if violated {
+            return Err(format!(
+                "{:?} schedule values are not monotonic at index {}",
+                monotonic,
+                i + 1
+            ));
+        }
+    }
+    Ok(())
+}
 // ------------------------- Extensions -------------------------------
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ExtensionNamespace {
-    #[serde(rename = "VariableSchedules", default)]
+    #[serde(
+        rename = "VariableSchedules",
+        default,
+        deserialize_with = "deserialize_entries"
+    )]
     pub variable_schedules: HashMap<String, ScheduleEntry>,
+    /// types private to this namespace, on top of the global specs
+    #[serde(rename = "VarTypeSpecs", default)]
+    pub var_type_specs: HashMap<String, VariableTypeSpec>,
     #[serde(flatten)]
-    pub extra: HashMap<String, JsonValue>
+    pub extra: HashMap<String, JsonValue>,
 }
 
-
 // ------------------------- Metadata Section -------------------------
 
-#[derive(Debug, Deserialize, Clone)]
+/// offset from UTC in whole hours, or an IANA timezone name (e.g.
+/// "America/New_York") whose daylight-saving changes T24 schedules follow
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TimezoneSpec {
+    Offset(i64),
+    Name(String),
+}
+
+impl Default for TimezoneSpec {
+    fn default() -> Self {
+        TimezoneSpec::Offset(0)
+    }
+}
+
+impl std::fmt::Display for TimezoneSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimezoneSpec::Offset(hours) => write!(f, "UTC{hours:+}"),
+            TimezoneSpec::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ScheduleInfo {
     #[serde(rename = "Version")]
     pub version: String,
     #[serde(rename = "Timezone", default)]
-    pub timezone: i64,
+    pub timezone: TimezoneSpec,
     #[serde(rename = "StartDate")]
     pub start_date: String,
     #[serde(rename = "StartOffset")]
@@ -160,11 +731,16 @@ pub struct ScheduleInfo {
     pub user: String,
     #[serde(rename = "Description")]
     pub description: String,
+    /// Site location in degrees, required by solar schedules
+    #[serde(rename = "Latitude", default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(rename = "Longitude", default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
     #[serde(rename = "Parents")]
     pub parents: ScheduleParents,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ScheduleParents {
     #[serde(rename = "Primary")]
     pub primary: String,
@@ -174,15 +750,22 @@ pub struct ScheduleParents {
 
 // ------------------------- Top-level Container -------------------------
 
+/// only the spec section of a schedule file, for reloading specs alone
+#[derive(Debug, Deserialize, Clone)]
+pub struct SpecsSection {
+    #[serde(rename = "VarTypeSpecs")]
+    pub var_type_specs: HashMap<String, VariableTypeSpec>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct LunaLuz {
-    #[serde(rename = "EventSchedules")]
-    pub event_schedules: HashMap<String, JsonValue>, // Placeholder for now
+    #[serde(rename = "EventSchedules", default)]
+    pub event_schedules: HashMap<String, EventEntry>,
 
     #[serde(rename = "VarTypeSpecs")]
     pub var_type_specs: HashMap<String, VariableTypeSpec>,
 
-    #[serde(rename = "VariableSchedules")]
+    #[serde(rename = "VariableSchedules", deserialize_with = "deserialize_entries")]
     pub variable_schedules: HashMap<String, ScheduleEntry>,
 
     #[serde(rename = "Info")]
@@ -191,3 +774,424 @@ pub struct LunaLuz {
     #[serde(rename = "Extensions", default)] // should be empty hashmap if not included
     pub extensions: HashMap<String, ExtensionNamespace>,
 }
+
+// ------------------------- File Formats -------------------------
+
+/// Encoding of a schedule file; YAML and TOML need their Cargo features
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl FileFormat {
+    /// format named by the file extension; JSON unless .yaml, .yml or .toml.
+    /// A trailing .gz is skipped, so `schedule.yaml.gz` is YAML
+    pub fn from_path(path: &Path) -> Self {
+        let path = match path.extension() {
+            Some(ext) if ext == "gz" => Path::new(path.file_stem().unwrap_or_default()),
+            _ => path,
+        };
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => FileFormat::Yaml,
+            Some("toml") => FileFormat::Toml,
+            _ => FileFormat::Json,
+        }
+    }
+
+    /// parse a document into a JSON value, which the schedule structs are
+    /// then deserialized from whatever the format
+    pub fn parse(self, text: &str) -> Result<JsonValue, String> {
+        match self {
+            FileFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            #[cfg(feature = "yaml")]
+            FileFormat::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "yaml"))]
+            FileFormat::Yaml => Err("YAML schedule files need the `yaml` feature".to_string()),
+            #[cfg(feature = "toml")]
+            FileFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "toml"))]
+            FileFormat::Toml => Err("TOML schedule files need the `toml` feature".to_string()),
+        }
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Text of a schedule file's contents, decompressed first if they start with
+/// the gzip magic bytes
+pub fn decode_schedule_bytes(data: Vec<u8>) -> io::Result<String> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+    #[cfg(feature = "gzip")]
+    {
+        use std::io::Read;
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data.as_slice()).read_to_string(&mut text)?;
+        Ok(text)
+    }
+    #[cfg(not(feature = "gzip"))]
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "gzip-compressed schedule files need the `gzip` feature",
+    ))
+}
+
+/// Read a schedule file, decompressing it if it is gzip-compressed
+pub fn read_schedule_file(path: &Path) -> io::Result<String> {
+    decode_schedule_bytes(std::fs::read(path)?)
+}
+
+/// Parse a schedule file, first checking it for unknown entry fields and
+/// mixed Period/Times styles if `strict`
+pub fn parse_schedule_file(
+    text: &str,
+    format: FileFormat,
+    strict: bool,
+) -> Result<LunaLuz, String> {
+    let raw = format.parse(text)?;
+    if strict {
+        check_strict_fields(&raw)?;
+    }
+    let file = serde_json::from_value(raw).map_err(|e| e.to_string())?;
+    if strict {
+        check_strict_styles(&file)?;
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(period: &str) -> String {
+        let led = format!(
+            r#""led": {{"VariableType": "intensity", "Period": {period}, "Times": [0, 12],
+                       "Values": [1, 0]}}"#
+        );
+        document(&led, "")
+    }
+
+    /// A schedule file with the given VariableSchedules and EventSchedules members
+    fn document(variables: &str, events: &str) -> String {
+        format!(
+            r#"{{
+                "Info": {{
+                    "Version": "1.0", "StartDate": "2024-01-01T00:00:00Z",
+                    "StartOffset": "PT0H", "ExperimentName": "Demo", "CabinetID": "C1",
+                    "User": "tester", "Description": "sample",
+                    "Parents": {{"Primary": "none", "Secondary": []}}
+                }},
+                "VarTypeSpecs": {{
+                    "intensity": {{"VariableType": "Ratio", "DefaultValue": 0, "Description": "light"}}
+                }},
+                "EventSchedules": {{ {events} }},
+                "VariableSchedules": {{ {variables} }}
+            }}"#
+        )
+    }
+
+    fn periodic(file: &LunaLuz) -> &PeriodicEntry {
+        match &file.variable_schedules["led"] {
+            ScheduleEntry::Periodic(entry) => entry,
+            entry => panic!("expected a periodic entry, got {entry:?}"),
+        }
+    }
+
+    #[test]
+    fn mixed_period_and_times_styles_warn() {
+        let parsed = parse_schedule_file(&file(r#""P1D""#), FileFormat::Json, false).unwrap();
+        let warning = periodic(&parsed).mixed_styles().unwrap();
+        assert!(warning.contains("P1D"), "{warning}");
+    }
+
+    #[test]
+    fn hour_and_calendar_periods_do_not_warn() {
+        for period in ["24", r#""P1M""#, r#""P1Y""#] {
+            let parsed = parse_schedule_file(&file(period), FileFormat::Json, false).unwrap();
+            assert_eq!(periodic(&parsed).mixed_styles(), None, "{period}");
+        }
+    }
+
+    #[test]
+    fn mixed_styles_are_rejected_when_strict() {
+        let error = parse_schedule_file(&file(r#""P1D""#), FileFormat::Json, true).unwrap_err();
+        assert!(error.contains("'led'"), "{error}");
+        assert!(parse_schedule_file(&file("24"), FileFormat::Json, true).is_ok());
+    }
+
+    #[test]
+    fn typo_in_a_schedule_field() {
+        let led = r#""led": {"VariableType": "intensity", "Period": 24, "Times": [0],
+                             "Values": [1], "Interpolaton": "linear"}"#;
+        let typo = document(led, "");
+        // an optional field, so only strict parsing notices
+        let parsed = parse_schedule_file(&typo, FileFormat::Json, false).unwrap();
+        assert_eq!(periodic(&parsed).interpolation, InterpolationMode::Floor);
+        let error = parse_schedule_file(&typo, FileFormat::Json, true).unwrap_err();
+        assert!(
+            error.contains("'led'") && error.contains("`Interpolaton`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn typo_in_an_event_field() {
+        let event = r#""pulse": {"VariableType": "intensity", "Events": [
+            {"Time": "2024-01-02T00:00:00Z", "Value": 1, "Duration": "PT1H", "Duraton": "PT2H"}
+        ]}"#;
+        let typo = document(
+            r#""lamp": {"VariableType": "intensity", "Value": 5}"#,
+            event,
+        );
+        assert!(parse_schedule_file(&typo, FileFormat::Json, false).is_ok());
+        let error = parse_schedule_file(&typo, FileFormat::Json, true).unwrap_err();
+        assert!(
+            error.contains("'pulse'") && error.contains("`Duraton`"),
+            "{error}"
+        );
+
+        let typo = typo.replace(r#""Events""#, r#""Evnets": [], "Events""#);
+        let error = parse_schedule_file(&typo, FileFormat::Json, true).unwrap_err();
+        assert!(error.contains("`Evnets`"), "{error}");
+    }
+
+    // struct literals, so a new field fails to compile here until it is
+    // added, and then fails the check until `known_fields` lists it
+    #[test]
+    fn known_fields_cover_every_entry_field() {
+        let header = ScheduleHeader {
+            variable_type: "intensity".to_string(),
+            schedule_type: None,
+            scale: Some(2.0),
+            offset: Some(1.0),
+        };
+        let entries = [
+            ScheduleEntry::Constant(ConstantEntry {
+                header: header.clone(),
+                value: JsonValue::from(1),
+            }),
+            ScheduleEntry::Periodic(PeriodicEntry {
+                header: header.clone(),
+                period: PeriodSpec::Hours(24.0),
+                times: vec![0.0],
+                values: vec![JsonValue::from(1)],
+                offset_time: Some(OffsetSpec::Hours(1.0)),
+                max_stale: Some("PT1H".to_string()),
+                monotonic: Some(Monotonic::Increasing),
+                solar: Some(Solar::Sunrise),
+                carry_in: true,
+                end_date: Some("2025-01-01T00:00:00Z".to_string()),
+                interpolation: InterpolationMode::Linear,
+                pre_start: PreStartBehavior::HoldLast,
+            }),
+            ScheduleEntry::Derived(DerivedEntry {
+                header: header.clone(),
+                formula: "a + b".to_string(),
+            }),
+            ScheduleEntry::Follow(FollowEntry {
+                header: header.clone(),
+                source: "a".to_string(),
+                delay: "PT1H".to_string(),
+                map: Some(HashMap::new()),
+            }),
+            ScheduleEntry::Absolute(AbsoluteEntry {
+                header: header.clone(),
+                breakpoints: Vec::new(),
+            }),
+            ScheduleEntry::Ramp(RampEntry {
+                header: header.clone(),
+                times: vec!["2024-01-01T00:00:00Z".to_string()],
+                values: vec![1.0],
+            }),
+            ScheduleEntry::Default(header),
+        ];
+        for entry in entries {
+            let mut raw = serde_json::to_value(&entry).unwrap();
+            // serialized entries leave their type to be inferred
+            raw["ScheduleType"] = serde_json::to_value(entry.schedule_type()).unwrap();
+            ScheduleEntry::check_fields(&raw).unwrap();
+        }
+
+        let event = EventEntry {
+            variable_type: "intensity".to_string(),
+            events: vec![EventPoint {
+                time: "2024-01-01T00:00:00Z".to_string(),
+                value: JsonValue::from(1),
+                duration: "PT1H".to_string(),
+            }],
+        };
+        EventEntry::check_fields(&serde_json::to_value(&event).unwrap()).unwrap();
+    }
+
+    fn entry(raw: JsonValue) -> ScheduleEntry {
+        ScheduleEntry::from_json(raw).unwrap()
+    }
+
+    #[test]
+    fn minutes_in_times_get_a_hint() {
+        let minutes = entry(serde_json::json!({
+            "VariableType": "intensity", "Period": 24, "Times": [0, 360], "Values": [1, 0]
+        }));
+        let error = minutes.is_valid().unwrap_err();
+        assert!(error.contains("index 1"), "{error}");
+        assert!(
+            error.contains("did you enter minutes instead of hours?"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn a_time_at_the_period_end_gets_no_hint() {
+        let off_by_one = entry(serde_json::json!({
+            "VariableType": "intensity", "Period": 24, "Times": [0, 24], "Values": [1, 0]
+        }));
+        let error = off_by_one.is_valid().unwrap_err();
+        assert!(error.contains("not within the period"), "{error}");
+        assert!(!error.contains("minutes"), "{error}");
+    }
+
+    #[test]
+    fn periodic_entry_missing_values() {
+        let led = r#""led": {"VariableType": "intensity", "Period": 24, "Times": [0, 12]}"#;
+        let error = parse_schedule_file(&document(led, ""), FileFormat::Json, false).unwrap_err();
+        assert!(error.contains("'led'"), "{error}");
+        assert!(error.contains("missing field `Values`"), "{error}");
+    }
+
+    #[test]
+    fn constant_entry_missing_value() {
+        let lamp = r#""lamp": {"VariableType": "intensity"}"#;
+        let error = parse_schedule_file(&document(lamp, ""), FileFormat::Json, false).unwrap_err();
+        assert!(error.contains("'lamp'"), "{error}");
+        assert!(error.contains("missing field `Value`"), "{error}");
+
+        let declared = r#""lamp": {"VariableType": "intensity", "ScheduleType": "default"}"#;
+        let parsed = parse_schedule_file(&document(declared, ""), FileFormat::Json, false).unwrap();
+        assert!(matches!(
+            parsed.variable_schedules["lamp"],
+            ScheduleEntry::Default(_)
+        ));
+    }
+
+    #[test]
+    fn constant_entry_with_a_mistyped_value() {
+        let lamp = r#""lamp": {"VariableType": "intensity", "Valeu": 5}"#;
+        let error = parse_schedule_file(&document(lamp, ""), FileFormat::Json, false).unwrap_err();
+        assert!(error.contains("'lamp'"), "{error}");
+        assert!(error.contains("unknown field `Valeu`"), "{error}");
+    }
+
+    #[test]
+    fn declared_type_must_match_the_fields() {
+        let lamp = r#""lamp": {"VariableType": "intensity", "ScheduleType": "constant",
+                              "Period": 24, "Times": [0], "Values": [1]}"#;
+        let error = parse_schedule_file(&document(lamp, ""), FileFormat::Json, false).unwrap_err();
+        assert!(error.contains("'lamp'"), "{error}");
+        assert!(
+            error.contains("specified schedule type (Constant); Periodic schedule was inferred"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn monotonic_values_that_repeat_are_valid() {
+        let ramp = entry(serde_json::json!({
+            "VariableType": "intensity", "Period": 24, "Times": [0, 6, 12, 18],
+            "Values": [0, 10, 10, 20], "Monotonic": "increasing"
+        }));
+        assert_eq!(ramp.is_valid(), Ok(()));
+    }
+
+    #[test]
+    fn monotonic_violation_names_its_index() {
+        let dip = entry(serde_json::json!({
+            "VariableType": "intensity", "Period": 24, "Times": [0, 6, 12],
+            "Values": [0, 10, 5], "Monotonic": "increasing"
+        }));
+        assert_eq!(
+            dip.is_valid().unwrap_err(),
+            "Increasing schedule values are not monotonic at index 2"
+        );
+        let rise = entry(serde_json::json!({
+            "VariableType": "intensity", "Period": 24, "Times": [0, 6],
+            "Values": [10, 20], "Monotonic": "decreasing"
+        }));
+        assert!(rise.is_valid().unwrap_err().contains("index 1"));
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        for (path, format) in [
+            ("schedule.json", FileFormat::Json),
+            ("schedule.yml", FileFormat::Yaml),
+            ("schedule.yaml.gz", FileFormat::Yaml),
+            ("schedule.toml", FileFormat::Toml),
+            ("schedule", FileFormat::Json),
+        ] {
+            assert_eq!(FileFormat::from_path(Path::new(path)), format, "{path}");
+        }
+    }
+
+    /// The sample file as a value, and `render`ed in another format
+    #[cfg(any(feature = "yaml", feature = "toml"))]
+    fn round_trip(format: FileFormat, render: impl Fn(&JsonValue) -> String) {
+        let json = file("24");
+        let value: JsonValue = serde_json::from_str(&json).unwrap();
+        let text = render(&value);
+        assert_eq!(format.parse(&text).unwrap(), value, "{text}");
+        let parsed = parse_schedule_file(&text, format, true).unwrap();
+        assert_eq!(periodic(&parsed).times, vec![0.0, 12.0]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trips_the_sample() {
+        round_trip(FileFormat::Yaml, |v| serde_yaml::to_string(v).unwrap());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trips_the_sample() {
+        round_trip(FileFormat::Toml, |v| toml::to_string(v).unwrap());
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn yaml_needs_its_feature() {
+        let error = FileFormat::Yaml.parse("Info: {}").unwrap_err();
+        assert!(error.contains("`yaml` feature"), "{error}");
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_files_load_like_plain_ones() {
+        let plain = file("24");
+        // detected by the magic bytes, whatever the file is called
+        let text = decode_schedule_bytes(gzip(&plain)).unwrap();
+        assert_eq!(text, plain);
+        let schedules = |text: &str| {
+            let file = parse_schedule_file(text, FileFormat::Json, false).unwrap();
+            let (schedules, _) = crate::schedules::parse_schedules(file).unwrap();
+            serde_json::to_value(schedules).unwrap()
+        };
+        assert_eq!(schedules(&text), schedules(&plain));
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn gzipped_files_need_the_feature() {
+        let error = decode_schedule_bytes(gzip(&file("24"))).unwrap_err();
+        assert!(error.to_string().contains("`gzip` feature"), "{error}");
+    }
+}
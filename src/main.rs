@@ -1,21 +1,73 @@
-mod lunaluz_deserialization;
-mod schedules;
-mod server_actions;
+#[cfg(feature = "openapi")]
+use luz_optimon::openapi;
+use luz_optimon::{
+    cache, clock, config, diff, explain, lunaluz_deserialization, metrics, rate_limit, schedules,
+    server_actions,
+};
 
-use std::{fs, net::{IpAddr, SocketAddr}, path::PathBuf, sync::Arc};
+use std::{
+    ffi::OsString,
+    future::Future,
+    io::{self, Read},
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
 
-use axum::{routing::{get, post}, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderValue, Method},
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
 
-use clap::Parser;
+use arc_swap::ArcSwap;
+use cache::ResponseCache;
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use clock::{FixedClock, SystemClock};
+use config::ServerConfig;
 use lunaluz_deserialization::*;
-use schedules::parse_schedules;
-use server_actions::{get_specs, get_vars, post_vars, AppState};
+use metrics::Metrics;
+use rate_limit::RateLimiter;
+use schedules::{parse_datetime_iso8601, parse_duration_iso8601, parse_schedules, ScheduleMap};
+use serde::{Serialize, Serializer};
+use server_actions::{
+    count_requests, get_api, get_clock, get_config, get_domain, get_export, get_health,
+    get_history, get_info, get_metrics, get_next_change, get_preview, get_snapshot_csv, get_spec,
+    get_specs, get_stats, get_stream, get_variables, get_vars, get_window, limit_rate, post_range,
+    post_vars, post_vars_batch, put_var, reload, reload_specs, require_admin_token,
+    require_api_key, AppState, FrozenSnapshot, LoadedSchedules, NamespaceFile, ServerStats,
+};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowHeaders, AllowOrigin, CorsLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::{info, Level, Span};
+use tracing_subscriber::EnvFilter;
 
-#[derive(Parser)]
-#[command(author, version, about)]
+#[derive(Parser, Serialize)]
+#[command(author, version, about, args_override_self = true)]
 struct Cli {
-    /// Path to the schedule file
-    filename: PathBuf,
+    /// Path to the schedule file, or `-` to read it from stdin; required
+    /// unless given by the config file
+    filename: Option<PathBuf>,
+
+    /// Read options from a TOML, YAML or JSON file whose keys are the option
+    /// names in snake_case (e.g. `cache_ttl`); flags given here override it
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Format of the schedule file, when its extension is misleading
+    /// (default: YAML for .yaml/.yml, TOML for .toml, otherwise JSON)
+    #[arg(long, value_enum)]
+    format: Option<FileFormat>,
 
     /// IP address to bind to (default: 127.0.0.1)
     #[arg(short, long, default_value = "127.0.0.1")]
@@ -24,34 +76,792 @@ struct Cli {
     /// Port number to bind to (default: 3000)
     #[arg(short, long, default_value_t = 3000)]
     port: u16,
+
+    /// Enable the /clock check, flagging the system clock if it is further
+    /// than this ISO 8601 duration before the start date (e.g. PT1H)
+    #[arg(long, value_parser = parse_duration_iso8601)]
+    #[serde(serialize_with = "serialize_seconds")]
+    clock_tolerance: Option<TimeDelta>,
+
+    /// Serialize numeric values in plain decimal notation instead of
+    /// scientific notation (e.g. 0.0000001 rather than 1e-7)
+    #[arg(long)]
+    plain_numbers: bool,
+
+    /// Maximum number of timestamps evaluated by a single request
+    #[arg(long, default_value_t = 10_000)]
+    max_times: usize,
+
+    /// Maximum request body size in bytes; larger requests get a 413
+    #[arg(long, default_value_t = 4 * 1024 * 1024)]
+    max_body_size: usize,
+
+    /// Bearer token required by /admin routes and by the routes changing the
+    /// served schedules (PUT /vars/{name}, /reload, /reload/specs); they are
+    /// all disabled without it
+    #[arg(long)]
+    #[serde(serialize_with = "redact")]
+    admin_token: Option<String>,
+
+    /// Require this key on every route but /health, as `Authorization:
+    /// Bearer <key>` or `X-API-Key: <key>`
+    #[arg(long)]
+    #[serde(serialize_with = "redact")]
+    api_key: Option<String>,
+
+    /// Limit each client IP to this many requests per second on every route
+    /// but /health; excess requests get a 429 with Retry-After
+    #[arg(long, value_parser = parse_rate)]
+    rate_limit: Option<f64>,
+
+    /// Requests a client may make at once before --rate-limit throttles it
+    /// (default: the rate, rounded up)
+    #[arg(long, requires = "rate_limit", value_parser = clap::value_parser!(u32).range(1..))]
+    rate_burst: Option<u32>,
+
+    /// Evaluate all variables once at this ISO 8601 time and serve that
+    /// snapshot from `GET /` and `GET /vars`; values never change, by design
+    /// (for load testing)
+    #[arg(long, value_parser = parse_datetime_iso8601)]
+    freeze_at: Option<DateTime<Utc>>,
+
+    /// Snap "now" query times down to a multiple of this ISO 8601 duration
+    /// (e.g. PT1M), so repeated polls within an interval are identical
+    #[arg(long, value_parser = parse_positive_duration)]
+    #[serde(serialize_with = "serialize_seconds")]
+    time_grid: Option<TimeDelta>,
+
+    /// Snap client-supplied query times to the --time-grid as well
+    #[arg(long, requires = "time_grid")]
+    snap_all_times: bool,
+
+    /// Cache `GET /` responses for this ISO 8601 duration (e.g. PT1S), so
+    /// identical queries arriving together are evaluated once
+    #[arg(long, value_parser = parse_positive_duration)]
+    #[serde(serialize_with = "serialize_seconds")]
+    cache_ttl: Option<TimeDelta>,
+
+    /// Maximum number of cached responses
+    #[arg(long, default_value_t = 1024)]
+    cache_size: usize,
+
+    /// Round query times down to a multiple of this ISO 8601 duration before
+    /// looking them up in the cache
+    #[arg(long, default_value = "PT1S", value_parser = parse_positive_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    cache_granularity: TimeDelta,
+
+    /// Load another schedule file as namespace NAME (NAME=PATH); repeatable
+    #[arg(long = "namespace", value_name = "NAME=PATH", value_parser = parse_namespace_file)]
+    namespace_files: Vec<NamespaceFile>,
+
+    /// Reject unknown fields in schedule entries and event schedules (e.g. a
+    /// typo'd "Interpolaton") instead of silently ignoring them, and periodic entries
+    /// mixing an ISO 8601 Period with Times in hours instead of warning about them
+    #[arg(long)]
+    strict_fields: bool,
+
+    /// Load and check the schedule and namespace files, print a summary and
+    /// exit without serving; exits non-zero if they don't load
+    #[arg(long)]
+    validate_only: bool,
+
+    /// Extension namespace used by requests that don't name one, instead of
+    /// the global namespace
+    #[arg(long)]
+    default_namespace: Option<String>,
+
+    /// Pin the server's clock to this ISO 8601 time, so requests without a
+    /// time are answered deterministically (for testing)
+    #[arg(long, value_parser = parse_datetime_iso8601)]
+    fixed_clock: Option<DateTime<Utc>>,
+
+    /// Allow cross-origin requests from this origin (repeatable, or `*` for
+    /// any); no CORS headers are sent without it
+    #[arg(long, value_parser = parse_cors_origin)]
+    cors_origin: Vec<String>,
+
+    /// Methods allowed in cross-origin requests, comma-separated
+    #[arg(long, value_delimiter = ',', default_value = "GET,POST", value_parser = parse_method)]
+    #[serde(serialize_with = "serialize_methods")]
+    cors_allow_methods: Vec<Method>,
+
+    /// Compress responses with gzip or brotli for clients that send a
+    /// matching Accept-Encoding (event streams are never compressed)
+    #[arg(long)]
+    compression: bool,
+
+    /// Log verbosity: a level such as `debug`, or a filter like
+    /// `luz_optimon=debug,info`; RUST_LOG takes precedence when set
+    #[arg(long, default_value = "info", value_parser = parse_log_level)]
+    log_level: String,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a step-by-step trace of how a variable's value is resolved
+    Explain {
+        /// Variable to explain
+        #[arg(long)]
+        var: String,
+        /// ISO 8601 time to evaluate at (default: now)
+        #[arg(long, value_parser = parse_datetime_iso8601)]
+        time: Option<DateTime<Utc>>,
+        /// Namespace ID (defaults to global namespace)
+        #[arg(long)]
+        namespace: Option<String>,
+    },
+    /// List variables added, removed or changed between two schedule files,
+    /// comparing their resolved schedules
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Parse and compile the schedule file and the `--namespace` files
+fn load_all(
+    args: &Cli,
+    file_data: &str,
+    format: FileFormat,
+) -> Result<(LunaLuz, LoadedSchedules), String> {
+    let parsed = parse_schedule_file(file_data, format, args.strict_fields)?;
+    let mut loaded = LoadedSchedules::new(parsed.clone()).map_err(|e| e.to_string())?;
+    for NamespaceFile { name, path } in &args.namespace_files {
+        read_schedule_file(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                parse_schedule_file(&data, FileFormat::from_path(path), args.strict_fields)
+            })
+            .and_then(|file| loaded.add_namespace(name, file))
+            .map_err(|e| format!("Failed to load namespace '{name}': {e}"))?;
+    }
+    Ok((parsed, loaded))
+}
+
+fn check_default_namespace(args: &Cli, loaded: &LoadedSchedules) -> Result<(), String> {
+    match &args.default_namespace {
+        Some(id) if !loaded.ext_schedules.contains_key(id) => {
+            Err(format!("Unknown default namespace: '{id}'"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `--validate-only` summary of what loaded
+fn validation_summary(parsed: &LunaLuz, loaded: &LoadedSchedules) -> Vec<String> {
+    let mut lines = vec![
+        format!("experiment: {}", parsed.info.experiment_name),
+        format!("global: {} variables", loaded.schedules.len()),
+    ];
+    let mut namespaces: Vec<_> = loaded.ext_schedules.iter().collect();
+    namespaces.sort_by_key(|(id, _)| *id);
+    for (id, namespace) in namespaces {
+        lines.push(format!("{id}: {} variables", namespace.schedules.len()));
+    }
+    lines
+}
+
+/// `--validate-only`: load everything a server would, print the summary or
+/// the error, and return the exit status
+fn validate(args: &Cli, filename: &Path, file_data: io::Result<String>, format: FileFormat) -> i32 {
+    let result = file_data
+        .map_err(|e| e.to_string())
+        .and_then(|data| load_all(args, &data, format))
+        .and_then(|(parsed, loaded)| {
+            check_default_namespace(args, &loaded)?;
+            Ok(validation_summary(&parsed, &loaded))
+        });
+    match result {
+        Ok(lines) => {
+            lines.iter().for_each(|line| println!("{line}"));
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", filename.display());
+            1
+        }
+    }
+}
+
+/// Resolved global schedules of the schedule file at `path`
+fn load_schedules(path: &Path, strict_fields: bool) -> Result<ScheduleMap, String> {
+    let file_data = read_schedule_file(path).map_err(|e| e.to_string())?;
+    let file = parse_schedule_file(&file_data, FileFormat::from_path(path), strict_fields)?;
+    let (schedules, _) = parse_schedules(file).map_err(|e| e.to_string())?;
+    Ok(schedules)
+}
+
+fn serialize_seconds<S: Serializer>(delta: &Option<TimeDelta>, s: S) -> Result<S::Ok, S::Error> {
+    delta.map(|d| d.num_seconds()).serialize(s)
+}
+
+fn serialize_duration<S: Serializer>(delta: &TimeDelta, s: S) -> Result<S::Ok, S::Error> {
+    serialize_seconds(&Some(*delta), s)
+}
+
+fn parse_positive_duration(input: &str) -> Result<TimeDelta, String> {
+    let duration = parse_duration_iso8601(input).map_err(|e| e.to_string())?;
+    if duration <= TimeDelta::zero() {
+        return Err("Duration must be positive".to_string());
+    }
+    Ok(duration)
+}
+
+/// Contents of the schedule file, or of `stdin` if the filename is `-`
+fn read_schedule_source(filename: &Path, mut stdin: impl Read) -> io::Result<String> {
+    if filename.as_os_str() != "-" {
+        return read_schedule_file(filename);
+    }
+    let mut data = Vec::new();
+    stdin.read_to_end(&mut data)?;
+    decode_schedule_bytes(data)
+}
+
+fn parse_namespace_file(input: &str) -> Result<NamespaceFile, String> {
+    let (name, path) = input
+        .split_once('=')
+        .ok_or_else(|| "Expected NAME=PATH".to_string())?;
+    if name.is_empty() {
+        return Err("Namespace name must not be empty".to_string());
+    }
+    Ok(NamespaceFile {
+        name: name.to_string(),
+        path: PathBuf::from(path),
+    })
+}
+
+fn parse_rate(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err("Rate must be a positive number of requests per second".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_cors_origin(input: &str) -> Result<String, String> {
+    HeaderValue::from_str(input).map_err(|e| format!("Invalid origin: {e}"))?;
+    Ok(input.to_string())
+}
+
+fn parse_method(input: &str) -> Result<Method, String> {
+    Method::from_bytes(input.trim().to_uppercase().as_bytes()).map_err(|e| e.to_string())
+}
+
+fn serialize_methods<S: Serializer>(methods: &[Method], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(methods.iter().map(Method::as_str))
+}
+
+/// CORS headers for the configured origins; None if no origin is allowed
+fn cors_layer(origins: &[String], methods: &[Method]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        // validated by parse_cors_origin
+        AllowOrigin::list(origins.iter().map(|o| HeaderValue::from_str(o).unwrap()))
+    };
+    let layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(methods.to_vec())
+        .allow_headers(AllowHeaders::mirror_request());
+    Some(layer)
+}
+
+fn parse_log_level(input: &str) -> Result<String, String> {
+    EnvFilter::try_new(input).map_err(|e| e.to_string())?;
+    Ok(input.to_string())
+}
+
+/// Span for a served request; the namespace is the one it resolves to
+fn request_span(request: &axum::extract::Request, default_namespace: Option<&str>) -> Span {
+    let query = request.uri().query().unwrap_or_default();
+    let namespace = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("namespace="))
+        .or(default_namespace)
+        .unwrap_or("global");
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        query,
+        namespace,
+    )
+}
+
+/// Completes on Ctrl-C, or on SIGTERM on Unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        signal(SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+fn redact<S: Serializer>(secret: &Option<String>, s: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(|_| "<redacted>").serialize(s)
+}
+
+/// `args` with the options of its `--config` file, if any, filled in ahead of
+/// the command line `argv`, so explicit flags override them
+fn with_config(args: Cli, argv: impl Iterator<Item = OsString>) -> Result<Cli, clap::Error> {
+    let Some(path) = &args.config else {
+        return Ok(args);
+    };
+    let (config, config_args) = ServerConfig::load(path)
+        .and_then(|config| {
+            let config_args = config.to_args(&Cli::command())?;
+            Ok((config, config_args))
+        })
+        .map_err(|e| {
+            Cli::command().error(ErrorKind::Io, format!("config {}: {e}", path.display()))
+        })?;
+    let mut argv = argv;
+    let merged = argv.next().into_iter().chain(config_args).chain(argv);
+    let mut args = Cli::try_parse_from(merged)?;
+    if args.filename.is_none() {
+        args.filename = config.filename;
+    }
+    Ok(args)
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Cli::parse();
+    let args = with_config(Cli::parse(), std::env::args_os()).unwrap_or_else(|e| e.exit());
+
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&args.log_level));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    if let Some(Command::Diff { old, new, json }) = &args.command {
+        let load = |path: &PathBuf| {
+            load_schedules(path, args.strict_fields).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(1);
+            })
+        };
+        let diff = diff::diff(&load(old), &load(new));
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+        } else {
+            diff.lines().iter().for_each(|line| println!("{line}"));
+        }
+        return;
+    }
+
+    let Some(filename) = args.filename.clone() else {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "a schedule file is required",
+            )
+            .exit()
+    };
+    let format = args
+        .format
+        .unwrap_or_else(|| FileFormat::from_path(&filename));
+    let file_data = read_schedule_source(&filename, io::stdin().lock());
+
+    if args.validate_only {
+        std::process::exit(validate(&args, &filename, file_data, format));
+    }
+
+    let file_data = file_data.unwrap();
+    let (parsed, loaded) = load_all(&args, &file_data, format).unwrap_or_else(|e| panic!("{e}"));
+
+    info!("Experiment Name: {}", parsed.info.experiment_name);
 
-    let json_data = fs::read_to_string(args.filename).unwrap();
-    let parsed: LunaLuz = serde_json::from_str(&json_data).unwrap();
+    let specs = parsed.var_type_specs.clone();
 
-    println!("Experiment Name: {}", parsed.info.experiment_name);
+    if let Some(Command::Explain {
+        var,
+        time,
+        namespace,
+    }) = &args.command
+    {
+        let schedules = match namespace {
+            Some(id) => match loaded.ext_schedules.get(id) {
+                Some(ext) => &ext.schedules,
+                None => {
+                    eprintln!("Unknown namespace: '{id}'");
+                    std::process::exit(1);
+                }
+            },
+            None => &loaded.schedules,
+        };
+        let time = time.unwrap_or_else(Utc::now);
+        match explain::explain(&parsed, schedules, namespace.as_deref(), var, time) {
+            Ok(lines) => lines.iter().for_each(|line| println!("{line}")),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let (map, ext_map) = parse_schedules(parsed.clone()).unwrap();
+    check_default_namespace(&args, &loaded).unwrap_or_else(|e| panic!("{e}"));
 
-    let state = AppState {
-        specs: parsed.var_type_specs,
-        schedules: Arc::new(map),
-        ext_schedules: Arc::new(ext_map)
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let mut state = AppState {
+        filename,
+        format,
+        namespace_files: args.namespace_files.clone(),
+        specs: Arc::new(RwLock::new(specs)),
+        loaded: Arc::new(ArcSwap::from_pointee(loaded)),
+        updates: Arc::new(Mutex::new(())),
+        strict_fields: args.strict_fields,
+        clock_tolerance: args.clock_tolerance,
+        plain_numbers: args.plain_numbers,
+        max_times: args.max_times,
+        admin_token: args.admin_token.clone(),
+        api_key: args.api_key.clone(),
+        rate_limiter: args.rate_limit.map(|rate| {
+            let burst = args.rate_burst.unwrap_or(rate.ceil() as u32);
+            Arc::new(RateLimiter::new(rate, burst))
+        }),
+        config: Arc::new(serde_json::to_value(&args).unwrap()),
+        frozen: None,
+        cache: args.cache_ttl.map(|ttl| {
+            let ttl = ttl
+                .to_std()
+                .expect("checked positive by parse_positive_duration");
+            Arc::new(ResponseCache::new(
+                ttl,
+                args.cache_size,
+                args.cache_granularity,
+            ))
+        }),
+        time_grid: args.time_grid,
+        snap_all_times: args.snap_all_times,
+        stats: Arc::new(ServerStats::new()),
+        metrics: Arc::new(Metrics::new()),
+        shutdown: shutdown_rx,
+        default_namespace: args.default_namespace.clone(),
+        clock: match args.fixed_clock {
+            Some(time) => Arc::new(FixedClock(time)),
+            None => Arc::new(SystemClock),
+        },
     };
 
-    let app = Router::new()
+    if let Some(time) = args.freeze_at {
+        let snapshot = FrozenSnapshot::new(&state, time).unwrap();
+        state.frozen = Some(Arc::new(snapshot));
+    }
+
+    let mut app = Router::new()
         .route("/", get(get_vars))
+        .route("/info", get(get_info))
         .route("/specs", get(get_specs))
+        .route("/specs/{var_type}", get(get_spec))
+        .route("/export", get(get_export))
+        .route("/variables", get(get_variables))
         .route("/vars", post(post_vars).get(get_vars))
-        .with_state(state);
+        .route("/vars/batch", post(post_vars_batch))
+        .route("/snapshot.csv", get(get_snapshot_csv))
+        .route("/preview/{var}", get(get_preview))
+        .route("/history/{var}", get(get_history))
+        .route("/window", get(get_window))
+        .route("/domain/{var}", get(get_domain))
+        .route("/next_change", get(get_next_change))
+        .route("/range", post(post_range))
+        .route("/stats", get(get_stats))
+        .route("/metrics", get(get_metrics))
+        .route("/stream", get(get_stream))
+        .route("/api", get(get_api));
+
+    if args.clock_tolerance.is_some() {
+        app = app.route("/clock", get(get_clock));
+    }
+    #[cfg(feature = "openapi")]
+    {
+        app = app.merge(openapi::swagger_ui());
+    }
+
+    if args.admin_token.is_some() {
+        let admin = Router::new()
+            .route("/admin/config", get(get_config))
+            .route("/vars/{name}", put(put_var))
+            .route("/reload", post(reload))
+            .route("/reload/specs", post(reload_specs))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_token,
+            ));
+        app = app.merge(admin);
+    }
+
+    if args.api_key.is_some() {
+        app = app.route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_api_key,
+        ));
+    }
+    // outside the API key check, so guessing keys is throttled too
+    if args.rate_limit.is_some() {
+        app = app.route_layer(middleware::from_fn_with_state(state.clone(), limit_rate));
+    }
+    // added after the API key and rate limit layers, so probes always work
+    let app = app.route("/health", get(get_health));
+
+    let app = app
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            count_requests,
+        ))
+        .layer(DefaultBodyLimit::max(args.max_body_size));
+    let app = if args.compression {
+        app.layer(CompressionLayer::new())
+    } else {
+        app
+    };
+    // outermost, so preflights are answered before reaching the routes
+    let app = match cors_layer(&args.cors_origin, &args.cors_allow_methods) {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
+    let default_namespace = args.default_namespace.clone();
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(move |request: &axum::extract::Request| {
+            request_span(request, default_namespace.as_deref())
+        })
+        .on_response(
+            DefaultOnResponse::new()
+                .level(Level::INFO)
+                .latency_unit(LatencyUnit::Micros),
+        );
+    let app = app.layer(trace).with_state(state);
 
     // run the app with hyper
     let socket = SocketAddr::new(args.ip, args.port);
-    let listener = tokio::net::TcpListener::bind(socket).await.expect("Failed to create TCP listener");
-    println!("Server is listening to {socket}, press Ctrl-C to exit program");
-    axum::serve(listener, app).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(socket)
+        .await
+        .expect("Failed to create TCP listener");
+    info!("Server is listening to {socket}, press Ctrl-C to exit program");
+    serve(listener, app, shutdown_signal(), shutdown)
+        .await
+        .unwrap();
+}
+
+/// Serve `app` until `signal` completes, then tell streams to end and let
+/// open requests finish
+async fn serve(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    signal: impl Future<Output = ()> + Send + 'static,
+    shutdown: watch::Sender<bool>,
+) -> io::Result<()> {
+    // the peer address keys the rate limit
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            signal.await;
+            info!("Shutting down, waiting for open requests to finish");
+            shutdown.send_replace(true);
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A schedule file with no variables
+    const EMPTY: &str = r#"{
+        "Info": {
+            "Version": "1.0", "StartDate": "2024-01-01T00:00:00Z", "StartOffset": "PT0H",
+            "ExperimentName": "Demo", "CabinetID": "C1", "User": "tester",
+            "Description": "sample", "Parents": {"Primary": "none", "Secondary": []}
+        },
+        "VarTypeSpecs": {},
+        "VariableSchedules": {}
+    }"#;
+
+    #[test]
+    fn effective_config_shows_flags_and_redacts_secrets() {
+        let args = Cli::parse_from([
+            "luz_optimon",
+            "schedule.json",
+            "--max-times",
+            "50",
+            "--admin-token",
+            "secret",
+        ]);
+        let config = serde_json::to_value(&args).unwrap();
+        assert_eq!(config["max_times"], 50);
+        assert_eq!(config["filename"], "schedule.json");
+        assert_eq!(config["admin_token"], "<redacted>");
+        assert_eq!(config["api_key"], serde_json::Value::Null);
+        assert!(!config.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn default_namespace_must_exist() {
+        let file = parse_schedule_file(EMPTY, FileFormat::Json, false).unwrap();
+        let mut loaded = LoadedSchedules::new(file.clone()).unwrap();
+        loaded.add_namespace("greenhouse", file).unwrap();
+        let args = |id: &str| Cli::parse_from(["luz_optimon", "f.json", "--default-namespace", id]);
+        assert!(check_default_namespace(&args("greenhouse"), &loaded).is_ok());
+        assert_eq!(
+            check_default_namespace(&args("barn"), &loaded).unwrap_err(),
+            "Unknown default namespace: 'barn'"
+        );
+    }
+
+    #[test]
+    fn dash_reads_the_schedule_from_stdin() {
+        let text = read_schedule_source(Path::new("-"), "piped schedule".as_bytes()).unwrap();
+        assert_eq!(text, "piped schedule");
+        // any other name is a file, and stdin is left unread
+        let error =
+            read_schedule_source(Path::new("/nonexistent/schedule.json"), "unused".as_bytes());
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn namespace_files_load_under_their_names() {
+        let path = |name: &str| {
+            let path = std::env::temp_dir()
+                .join(format!("luz_optimon_{}_{name}.json", std::process::id()));
+            std::fs::write(&path, EMPTY).unwrap();
+            path.display().to_string()
+        };
+        let (a, b) = (format!("a={}", path("ns_a")), format!("b={}", path("ns_b")));
+        let args = Cli::parse_from([
+            "luz_optimon",
+            "f.json",
+            "--namespace",
+            &a,
+            "--namespace",
+            &b,
+        ]);
+        let (_, loaded) = load_all(&args, EMPTY, FileFormat::Json).unwrap();
+        let mut names: Vec<_> = loaded.ext_schedules.keys().collect();
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+
+        let twice = Cli::parse_from([
+            "luz_optimon",
+            "f.json",
+            "--namespace",
+            &a,
+            "--namespace",
+            &a,
+        ]);
+        let error = load_all(&twice, EMPTY, FileFormat::Json).err().unwrap();
+        assert!(error.contains("already defined"), "{error}");
+        assert!(parse_namespace_file("=x.json").is_err());
+        assert!(parse_namespace_file("x.json").is_err());
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_allows_the_configured_origin() {
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+
+        let origin = "https://dash.example";
+        let cors = cors_layer(&[origin.to_string()], &[Method::GET, Method::POST]).unwrap();
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(cors);
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(preflight).await.unwrap();
+        let headers = response.headers();
+        assert_eq!(headers["access-control-allow-origin"], origin);
+        assert_eq!(headers["access-control-allow-methods"], "GET,POST");
+
+        assert!(cors_layer(&[], &[Method::GET]).is_none());
+    }
+
+    #[tokio::test]
+    async fn serving_ends_when_the_shutdown_signal_fires() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let (fire, signal) = tokio::sync::oneshot::channel::<()>();
+        let (shutdown, mut streams) = watch::channel(false);
+        let server = tokio::spawn(serve(
+            listener,
+            app,
+            async {
+                signal.await.ok();
+            },
+            shutdown,
+        ));
+
+        fire.send(()).unwrap();
+        let served = tokio::time::timeout(std::time::Duration::from_secs(5), server).await;
+        served.expect("server kept running").unwrap().unwrap();
+        // open streams are told to end
+        assert!(*streams.borrow_and_update());
+    }
+
+    #[test]
+    fn validation_exits_by_whether_the_file_loads() {
+        let args = Cli::parse_from(["luz_optimon", "f.json", "--validate-only"]);
+        let path = Path::new("f.json");
+        let status = |data: &str| validate(&args, path, Ok(data.to_string()), FileFormat::Json);
+        assert_eq!(status(EMPTY), 0);
+        assert_ne!(status(&EMPTY.replace("StartDate", "Start")), 0);
+        assert_ne!(status("{not json"), 0);
+        let unreadable = Err(io::Error::from(io::ErrorKind::NotFound));
+        assert_ne!(validate(&args, path, unreadable, FileFormat::Json), 0);
+    }
+
+    #[test]
+    fn config_files_supply_options_that_flags_override() {
+        let config =
+            std::env::temp_dir().join(format!("luz_optimon_{}_config.json", std::process::id()));
+        std::fs::write(&config, r#"{"filename": "schedule.json", "port": 9000}"#).unwrap();
+        let config = config.display().to_string();
+        let run = |argv: &[&str]| {
+            let argv: Vec<OsString> = argv.iter().map(OsString::from).collect();
+            with_config(Cli::parse_from(&argv), argv.into_iter())
+        };
+
+        let args = run(&["luz_optimon", "--config", &config]).unwrap();
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.filename, Some(PathBuf::from("schedule.json")));
+        let args = run(&[
+            "luz_optimon",
+            "other.json",
+            "--config",
+            &config,
+            "--port",
+            "7000",
+        ])
+        .unwrap();
+        assert_eq!(args.port, 7000);
+        assert_eq!(args.filename, Some(PathBuf::from("other.json")));
+
+        let missing = run(&["luz_optimon", "--config", "/nonexistent/server.toml"]);
+        assert_eq!(missing.err().unwrap().kind(), ErrorKind::Io);
+    }
 }
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics served by `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    /// requests by route and status
+    requests: IntCounterVec,
+    /// 4xx and 5xx responses by route and status
+    errors: IntCounterVec,
+    /// seconds spent handling requests, by route
+    latency: HistogramVec,
+    /// value queries by resolved namespace ("global" for the global one)
+    namespace_queries: IntCounterVec,
+    /// variables returned per value query
+    response_variables: Histogram,
+    /// set when scraped, from the currently loaded schedules
+    pub variables: IntGauge,
+    pub namespaces: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let labels = &["route", "status"];
+        let requests =
+            IntCounterVec::new(Opts::new("luz_requests_total", "Requests served"), labels).unwrap();
+        let errors = IntCounterVec::new(
+            Opts::new("luz_errors_total", "Requests answered with an error status"),
+            labels,
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("luz_request_duration_seconds", "Request latency"),
+            &["route"],
+        )
+        .unwrap();
+        let namespace_queries = IntCounterVec::new(
+            Opts::new("luz_namespace_queries_total", "Value queries by namespace"),
+            &["namespace"],
+        )
+        .unwrap();
+        let response_variables = Histogram::with_opts(
+            HistogramOpts::new(
+                "luz_response_variables",
+                "Variables returned per value query",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 7).unwrap()),
+        )
+        .unwrap();
+        let variables =
+            IntGauge::new("luz_loaded_variables", "Variables in the global namespace").unwrap();
+        let namespaces =
+            IntGauge::new("luz_loaded_namespaces", "Loaded extension namespaces").unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry
+            .register(Box::new(namespace_queries.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(response_variables.clone()))
+            .unwrap();
+        registry.register(Box::new(variables.clone())).unwrap();
+        registry.register(Box::new(namespaces.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            errors,
+            latency,
+            namespace_queries,
+            response_variables,
+            variables,
+            namespaces,
+        }
+    }
+
+    /// Record one served request; `route` is the matched route pattern
+    pub fn observe(&self, route: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        self.requests.with_label_values(&[route, &status]).inc();
+        if status.starts_with(['4', '5']) {
+            self.errors.with_label_values(&[route, &status]).inc();
+        }
+        self.latency
+            .with_label_values(&[route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Record one value query against `namespace` returning `variables` values
+    pub fn observe_query(&self, namespace: &str, variables: usize) {
+        self.namespace_queries.with_label_values(&[namespace]).inc();
+        self.response_variables.observe(variables as f64);
+    }
+
+    /// All metrics in the Prometheus text exposition format
+    pub fn render(&self) -> Result<String, String> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buf).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,63 @@
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::server_actions;
+
+/// OpenAPI document of the main query routes; `/api` lists every route
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "luz_optimon",
+        description = "Schedule values of a lunaluz experiment"
+    ),
+    paths(
+        server_actions::get_vars,
+        server_actions::post_vars,
+        server_actions::get_specs,
+        server_actions::get_spec,
+        server_actions::get_health,
+    )
+)]
+pub struct ApiDoc;
+
+/// `/openapi.json` plus a Swagger UI for it at `/docs`
+pub fn swagger_ui() -> SwaggerUi {
+    SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn openapi_json_documents_the_query_routes() {
+        let app: Router = Router::new().merge(swagger_ui());
+        let request = Request::get("/openapi.json").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let doc: Value = serde_json::from_slice(&body).unwrap();
+
+        assert!(doc["paths"]["/vars"]["post"].is_object(), "{doc}");
+        let params: Vec<&str> = doc["paths"]["/"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        for name in ["time", "namespace"] {
+            assert!(params.contains(&name), "{name} in {params:?}");
+        }
+        let query = &doc["components"]["schemas"]["ScheduleQuery"]["properties"];
+        for field in ["times", "vars", "include_types"] {
+            assert!(query[field].is_object(), "{field} in {query}");
+        }
+    }
+}
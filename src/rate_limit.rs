@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Clients tracked before buckets that have refilled are dropped
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Token bucket of one client
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-client-IP token buckets: each client may make `burst` requests at
+/// once, refilled at `rate` requests per second
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: burst.into(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for a request from `client`; Err with how long until one
+    /// is available if the bucket is empty
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+}
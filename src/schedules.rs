@@ -1,23 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use chrono::{DateTime, Datelike, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Months, NaiveDateTime, NaiveTime, Offset, SecondsFormat,
+    TimeDelta, TimeZone, Utc,
+};
 use enum_dispatch::enum_dispatch;
+use evalexpr::{
+    build_operator_tree, ContextWithMutableVariables, DefaultNumericTypes, HashMapContext, Node,
+    Value as EvalValue,
+};
+use serde::{Serialize, Serializer};
 use serde_json::Value;
+use sunrise::{Coordinates, SolarDay, SolarEvent};
 
-use crate::lunaluz_deserialization::{LunaLuz, ScheduleEntry};
+use crate::error::ScheduleError;
+use crate::lunaluz_deserialization::{
+    AbsoluteEntry, Breakpoint, ConstantEntry, DerivedEntry, EventEntry, FollowEntry,
+    InterpolationMode, LunaLuz, OffsetSpec, PeriodSpec, PeriodicEntry, PreStartBehavior, RampEntry,
+    ScheduleEntry, ScheduleHeader, ScheduleInfo, Solar, TimezoneSpec, VarDataType,
+    VariableTypeSpec,
+};
 
-pub fn midnight(time: &DateTime<Utc>) -> DateTime<Utc> {
-    // retrieve datetime for very start of a given day
-    time.timezone()
-        .with_ymd_and_hms(time.year(), time.month(), time.day(), 0, 0, 0)
-        .unwrap() // should never happen since derived from &Datetime<Utc>
+pub fn parse_datetime_iso8601(input: &str) -> Result<DateTime<Utc>, ScheduleError> {
+    parse_datetime_in(input, &Zone::default())
 }
 
-pub fn parse_datetime_iso8601(input: &str) -> Result<DateTime<Utc>, String> {
+/// Parse an ISO 8601 time, reading it in `tz` when it has no offset of its own
+pub fn parse_datetime_in(input: &str, tz: &Zone) -> Result<DateTime<Utc>, ScheduleError> {
     // Attempt RFC 3339 / ISO 8601 extended first
     let result = DateTime::parse_from_rfc3339(input)
         .map(|dt| dt.with_timezone(&Utc))
-        .map_err(|e| format!("Error parsing time: {e}"));
+        .map_err(|e| ScheduleError::InvalidDateTime(e.to_string()));
     if result.is_ok() {
         return result;
     }
@@ -31,7 +44,7 @@ pub fn parse_datetime_iso8601(input: &str) -> Result<DateTime<Utc>, String> {
 
     for format in FORMATS {
         if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
-            return Ok(Utc.from_utc_datetime(&naive));
+            return tz.resolve(&naive);
         }
     }
 
@@ -39,48 +52,461 @@ pub fn parse_datetime_iso8601(input: &str) -> Result<DateTime<Utc>, String> {
     result
 }
 
-fn parse_duration_iso8601(dur: &str) -> Result<TimeDelta, String> {
+/// A fixed UTC offset, or (with the `tz` feature) an IANA timezone with its
+/// daylight-saving changes
+#[derive(Debug, Clone, Copy)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Zone::Fixed(Utc.fix())
+    }
+}
+
+impl Zone {
+    /// `"utc"`, an offset in hours such as `"-5"`, an offset such as
+    /// `"-05:00"`, or (with the `tz` feature) an IANA name like `"America/New_York"`
+    pub fn parse(input: &str) -> Result<Self, ScheduleError> {
+        let invalid = |message: String| ScheduleError::InvalidInputTimezone {
+            input: input.to_owned(),
+            message,
+        };
+        if input.eq_ignore_ascii_case("utc") {
+            return Ok(Self::default());
+        }
+        if let Ok(hours) = input.parse::<f64>() {
+            return Some(hours * 3600.0)
+                .filter(|secs| secs.is_finite())
+                .and_then(|secs| FixedOffset::east_opt(secs.round() as i32))
+                .map(Zone::Fixed)
+                .ok_or_else(|| invalid("offset out of range".to_string()));
+        }
+        if let Ok(offset) = input.parse::<FixedOffset>() {
+            return Ok(Zone::Fixed(offset));
+        }
+        Self::named(input).map_err(invalid)
+    }
+
+    #[cfg(feature = "tz")]
+    fn named(input: &str) -> Result<Self, String> {
+        input
+            .parse::<chrono_tz::Tz>()
+            .map(Zone::Named)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "tz"))]
+    fn named(_input: &str) -> Result<Self, String> {
+        Err("expected an offset; timezone names need the `tz` feature".to_string())
+    }
+
+    /// The schedule file's Info Timezone
+    pub fn from_spec(spec: &TimezoneSpec) -> Result<Self, ScheduleError> {
+        match spec {
+            TimezoneSpec::Offset(hours) => i32::try_from(hours * 3600)
+                .ok()
+                .and_then(FixedOffset::east_opt)
+                .map(Zone::Fixed)
+                .ok_or(ScheduleError::TimezoneOutOfRange),
+            TimezoneSpec::Name(name) => {
+                Self::named(name).map_err(|message| ScheduleError::UnknownTimezone {
+                    name: name.clone(),
+                    message,
+                })
+            }
+        }
+    }
+
+    /// whether the offset ever changes
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, Zone::Fixed(_))
+    }
+
+    /// offset from UTC in effect at `time`
+    #[cfg_attr(not(feature = "tz"), allow(unused_variables))]
+    pub fn offset_at(&self, time: &DateTime<Utc>) -> FixedOffset {
+        match self {
+            Zone::Fixed(offset) => *offset,
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => tz.offset_from_utc_datetime(&time.naive_utc()).fix(),
+        }
+    }
+
+    /// wall-clock time at `time`
+    pub fn local(&self, time: &DateTime<Utc>) -> NaiveDateTime {
+        time.naive_utc() + self.offset_at(time)
+    }
+
+    /// like `resolve`, but a wall-clock time skipped when clocks go forward
+    /// is read with the offset from before the change
+    pub fn instant(&self, naive: &NaiveDateTime) -> DateTime<Utc> {
+        self.resolve(naive).unwrap_or_else(|_| {
+            let before = self.offset_at(&(naive.and_utc() - TimeDelta::days(1)));
+            naive.and_utc() - before
+        })
+    }
+
+    /// UTC time of the local wall-clock time `naive`; times repeated when
+    /// clocks go back resolve to the earlier one
+    pub fn resolve(&self, naive: &NaiveDateTime) -> Result<DateTime<Utc>, ScheduleError> {
+        let local = match self {
+            Zone::Fixed(offset) => offset.from_local_datetime(naive).map(|t| t.to_utc()),
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => tz.from_local_datetime(naive).map(|t| t.to_utc()),
+        };
+        local
+            .earliest()
+            .ok_or_else(|| ScheduleError::NonexistentLocalTime(naive.to_string()))
+    }
+}
+
+/// an offset like "-05:00", or the IANA name
+impl Serialize for Zone {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Zone::Fixed(offset) => s.collect_str(offset),
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => s.serialize_str(tz.name()),
+        }
+    }
+}
+
+/// Durations in exported schedules are in seconds
+fn serialize_seconds<S: Serializer>(delta: &TimeDelta, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(td_nanos(*delta) as f64 / 1e9)
+}
+
+fn serialize_opt_seconds<S: Serializer>(
+    delta: &Option<TimeDelta>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match delta {
+        Some(delta) => serialize_seconds(delta, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn serialize_all_seconds<S: Serializer>(deltas: &[TimeDelta], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(deltas.iter().map(|d| td_nanos(*d) as f64 / 1e9))
+}
+
+/// Convert (possibly fractional) Unix epoch seconds to a datetime
+pub fn datetime_from_epoch(seconds: f64) -> Result<DateTime<Utc>, ScheduleError> {
+    let whole = seconds.floor();
+    if !whole.is_finite() || whole.abs() > i64::MAX as f64 {
+        return Err(ScheduleError::EpochOutOfRange(seconds));
+    }
+    let nanos = ((seconds - whole) * 1e9).round().min(999_999_999.0) as u32;
+    DateTime::from_timestamp(whole as i64, nanos).ok_or(ScheduleError::EpochOutOfRange(seconds))
+}
+
+/// Parse a query time: an ISO 8601 time as in [`parse_datetime_in`], or Unix
+/// epoch seconds written as bare digits with an optional `.` fraction, e.g.
+/// `1700000000` or `1700000000.25`. ISO basic formats always carry a `T`, so
+/// an all-digit string can only be an epoch
+pub fn parse_query_time(input: &str, tz: &Zone) -> Result<DateTime<Utc>, ScheduleError> {
+    let (whole, fraction) = input.split_once('.').unwrap_or((input, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !(is_digits(whole) && is_digits(fraction)) {
+        return parse_datetime_in(input, tz);
+    }
+    let seconds: f64 = input
+        .parse()
+        .map_err(|_| ScheduleError::InvalidDateTime(input.to_string()))?;
+    datetime_from_epoch(seconds)
+}
+
+/// Resolve an output timezone: `"utc"`, `"local"` (the schedule's configured
+/// offset), or an explicit fixed offset such as `"-05:00"`
+pub fn parse_out_tz(input: &str, local: FixedOffset) -> Result<FixedOffset, ScheduleError> {
+    match input.to_ascii_lowercase().as_str() {
+        "utc" => Ok(Utc.fix()),
+        "local" => Ok(local),
+        _ => input
+            .parse::<FixedOffset>()
+            .map_err(|e| ScheduleError::InvalidTimezone {
+                input: input.to_owned(),
+                message: e.to_string(),
+            }),
+    }
+}
+
+pub fn parse_duration_iso8601(dur: &str) -> Result<TimeDelta, ScheduleError> {
     let raw_duration = dur
         .parse::<iso8601_duration::Duration>()
-        .map_err(|e| format!("Invalid time duration: {e:?}"))?;
+        .map_err(|e| ScheduleError::InvalidDuration(format!("{e:?}")))?;
 
     let std_duration = raw_duration
         .to_std()
-        .ok_or_else(|| ("Duration contains unsupported units (e.g. months, years)".to_string()))?;
+        .ok_or(ScheduleError::UnsupportedDurationUnit)?;
 
-    TimeDelta::from_std(std_duration)
-        .map_err(|e| format!("Failed to convert std Duration to TimeDelta: {e}"))
+    Ok(TimeDelta::from_std(std_duration)?)
 }
 
-pub fn hours_to_td(hours: f64) -> Result<TimeDelta, String> {
+fn hours_to_td(hours: f64) -> Result<TimeDelta, ScheduleError> {
     let seconds = hours * 3.6e3;
-    let duration = std::time::Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())?;
-    TimeDelta::from_std(duration).map_err(|e| e.to_string())
+
+    // snap whole seconds (incl. whole minutes/hours) to an exact TimeDelta so
+    // float error doesn't accumulate when projected across many periods
+    let whole = seconds.round();
+    if whole >= 0.0 && (seconds - whole).abs() < 1e-6 && whole < i64::MAX as f64 {
+        return TimeDelta::try_seconds(whole as i64).ok_or(ScheduleError::DurationOutOfRange);
+    }
+
+    let duration = std::time::Duration::try_from_secs_f64(seconds)?;
+    Ok(TimeDelta::from_std(duration)?)
 }
 
-pub fn convert_times(times: Vec<f64>) -> Result<Vec<TimeDelta>, String> {
+fn convert_times(times: Vec<f64>) -> Result<Vec<TimeDelta>, ScheduleError> {
     times.into_iter().map(hours_to_td).collect()
 }
+
+pub(crate) fn td_nanos(delta: TimeDelta) -> i128 {
+    delta.num_seconds() as i128 * 1_000_000_000 + delta.subsec_nanos() as i128
+}
+
+fn td_from_nanos(nanos: i128) -> Option<TimeDelta> {
+    let seconds = i64::try_from(nanos.div_euclid(1_000_000_000)).ok()?;
+    TimeDelta::new(seconds, nanos.rem_euclid(1_000_000_000) as u32)
+}
 #[enum_dispatch(Schedule)]
 pub trait VarSchedule {
     fn var_type(&self) -> String;
     fn floor_search(&self, time: &DateTime<Utc>) -> Value;
 
+    /// value at `time` interpolated between schedule points; schedules without
+    /// interpolation hold the previous value
+    fn interp_search(&self, time: &DateTime<Utc>) -> Value {
+        self.floor_search(time)
+    }
+
+    /// value of the next schedule point at or after `time`; schedules without
+    /// points hold their current value
+    fn ceil_search(&self, time: &DateTime<Utc>) -> Value {
+        self.floor_search(time)
+    }
+
+    /// value of whichever schedule point is closest to `time`, the earlier
+    /// one on ties; schedules without points hold their current value
+    fn nearest_search(&self, time: &DateTime<Utc>) -> Value {
+        self.floor_search(time)
+    }
+
+    /// true if every value this schedule can produce is a JSON number
+    fn is_numeric(&self) -> bool;
+    /// distinct values this schedule can produce; None if unbounded
+    fn domain(&self) -> Option<Vec<Value>>;
+    /// start point and period, for schedules that repeat
+    fn cycle(&self) -> Option<(DateTime<Utc>, TimeDelta)> {
+        None
+    }
+    /// sorted instants in `(start, end)` where the value may change (possibly
+    /// including some where it doesn't); stops after `limit + 1` instants
+    fn change_points(
+        &self,
+        _start: &DateTime<Utc>,
+        _end: &DateTime<Utc>,
+        _limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        Vec::new()
+    }
+
     fn floor_multi_search(&self, times: &[DateTime<Utc>]) -> Vec<Value> {
         times.iter().map(|t| self.floor_search(t)).collect()
     }
+
+    /// first instant after `time` at which the value changes; None if it
+    /// never does. Repeating schedules are searched for a few periods (their
+    /// values repeat after that), others up to `NEXT_CHANGE_HORIZON` ahead
+    fn next_change(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let end = match self.cycle() {
+            Some((start_point, period)) => (*time).max(start_point) + period * 3,
+            None => *time + NEXT_CHANGE_HORIZON,
+        };
+        let current = self.floor_search(time);
+        let mut from = *time;
+        loop {
+            let points = self.change_points(&from, &end, NEXT_CHANGE_BATCH);
+            if let Some(change) = points.iter().find(|t| self.floor_search(t) != current) {
+                return Some(*change);
+            }
+            // fewer points than asked for means none are left before `end`
+            if points.len() <= NEXT_CHANGE_BATCH {
+                return None;
+            }
+            from = *points.last()?;
+        }
+    }
 }
 
+/// How far ahead `next_change` searches schedules that don't repeat
+pub const NEXT_CHANGE_HORIZON: TimeDelta = TimeDelta::days(366);
+const NEXT_CHANGE_BATCH: usize = 1024;
+
 // ! TODO: add tests for each of these both before and after start/end, etc.
 
-#[derive(Debug)]
+/// Serialized (for `/export`) with its fields under a `kind` tag
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
 #[enum_dispatch]
 pub enum Schedule {
     Constant(ConstantSchedule),
     Periodic(PeriodicSchedule),
+    Derived(DerivedSchedule),
+    Follow(FollowSchedule),
+    Absolute(AbsoluteSchedule),
+    Event(EventSchedule),
+    Ramp(RampSchedule),
+    Transformed(TransformedSchedule),
+}
+
+impl Schedule {
+    /// the schedule without its output Scale/Offset, if it has one
+    pub fn raw(&self) -> &Schedule {
+        match self {
+            Schedule::Transformed(transformed) => &transformed.inner,
+            schedule => schedule,
+        }
+    }
+
+    /// name of the schedule's type, as listed by `/variables`
+    pub fn kind(&self) -> &'static str {
+        match self.raw() {
+            Schedule::Constant(_) => "constant",
+            Schedule::Periodic(_) => "periodic",
+            Schedule::Derived(_) => "derived",
+            Schedule::Follow(_) => "follow",
+            Schedule::Absolute(_) => "absolute",
+            Schedule::Event(_) => "event",
+            Schedule::Ramp(_) => "ramp",
+            Schedule::Transformed(transformed) => transformed.inner.kind(),
+        }
+    }
+
+    /// names of the variables the schedule was resolved from
+    pub fn references(&self) -> Vec<&str> {
+        match self.raw() {
+            Schedule::Derived(derived) => derived.inputs.iter().map(|(n, _)| n.as_str()).collect(),
+            Schedule::Follow(follow) => vec![follow.target.as_str()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `VariableSchedules` entry this schedule resolves from, up to float
+    /// precision; `start_date` is the file's Info StartDate, which a periodic
+    /// schedule's OffsetTime is relative to. None for event schedules, which
+    /// come from `EventSchedules`. Type defaults come back as constants and
+    /// `Monotonic`, checked only at load, is not kept
+    ///
+    /// ```
+    /// use luz_optimon::{parse_datetime_iso8601, parse_schedule_file, parse_schedules, FileFormat};
+    ///
+    /// let file = r#"{
+    ///     "Info": {
+    ///         "Version": "1.0", "Timezone": 0, "StartDate": "2024-01-01T00:00:00Z",
+    ///         "StartOffset": "PT0H", "ExperimentName": "Demo", "CabinetID": "C1",
+    ///         "User": "tester", "Description": "sample",
+    ///         "Parents": {"Primary": "none", "Secondary": []}
+    ///     },
+    ///     "VarTypeSpecs": {
+    ///         "intensity": {"VariableType": "Ratio", "DefaultValue": 0, "Description": "light"}
+    ///     },
+    ///     "VariableSchedules": {
+    ///         "led": {"VariableType": "intensity", "Period": 2.5, "Times": [0, 1.25],
+    ///                 "Values": [100, 0], "OffsetTime": "PT30M", "MaxStale": "PT1H"},
+    ///         "lux": {"VariableType": "intensity", "Formula": "led * 2", "Scale": 10},
+    ///         "echo": {"VariableType": "intensity", "Source": "led", "Delay": "PT15M"},
+    ///         "ramp": {"VariableType": "intensity", "Times": ["2024-01-01T00:00:00Z",
+    ///                  "2024-01-02T00:00:00Z"], "Values": [0, 50]}
+    ///     }
+    /// }"#;
+    /// let mut file = parse_schedule_file(file, FileFormat::Json, false).unwrap();
+    /// let start = parse_datetime_iso8601(&file.info.start_date).unwrap();
+    /// let (schedules, _) = parse_schedules(file.clone()).unwrap();
+    ///
+    /// file.variable_schedules = schedules
+    ///     .iter()
+    ///     .map(|(name, schedule)| (name.clone(), schedule.to_entry(start).unwrap()))
+    ///     .collect();
+    /// let (rebuilt, _) = parse_schedules(file).unwrap();
+    /// for (name, schedule) in &schedules {
+    ///     let export = |s| serde_json::to_value(s).unwrap();
+    ///     assert_eq!(export(schedule), export(&rebuilt[name]), "{name}");
+    /// }
+    /// ```
+    pub fn to_entry(&self, start_date: DateTime<Utc>) -> Option<ScheduleEntry> {
+        let header = ScheduleHeader {
+            variable_type: self.var_type(),
+            schedule_type: None,
+            scale: None,
+            offset: None,
+        };
+        let entry = match self {
+            Schedule::Constant(constant) => ScheduleEntry::Constant(ConstantEntry {
+                header,
+                value: constant.value.clone(),
+            }),
+            Schedule::Periodic(periodic) => {
+                ScheduleEntry::Periodic(periodic.to_entry(header, start_date))
+            }
+            Schedule::Derived(derived) => ScheduleEntry::Derived(DerivedEntry {
+                header,
+                formula: derived.formula.clone(),
+            }),
+            Schedule::Follow(follow) => ScheduleEntry::Follow(FollowEntry {
+                header,
+                source: follow.target.clone(),
+                delay: iso8601_duration(follow.delay),
+                map: follow.map.clone(),
+            }),
+            Schedule::Absolute(absolute) => ScheduleEntry::Absolute(AbsoluteEntry {
+                header,
+                breakpoints: (absolute.breakpoints.iter())
+                    .map(|(time, value)| Breakpoint {
+                        time: iso8601_time(time),
+                        value: value.clone(),
+                    })
+                    .collect(),
+            }),
+            Schedule::Event(_) => return None,
+            Schedule::Ramp(ramp) => ScheduleEntry::Ramp(RampEntry {
+                header,
+                times: ramp
+                    .anchors
+                    .iter()
+                    .map(|(time, _)| iso8601_time(time))
+                    .collect(),
+                values: ramp.anchors.iter().map(|(_, value)| *value).collect(),
+            }),
+            Schedule::Transformed(transformed) => {
+                let mut entry = transformed.inner.to_entry(start_date)?;
+                let header = entry.header_mut();
+                header.scale = Some(transformed.transform.scale);
+                header.offset = Some(transformed.transform.offset);
+                entry
+            }
+        };
+        Some(entry)
+    }
+}
+
+fn iso8601_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn iso8601_duration(delta: TimeDelta) -> String {
+    format!("PT{}S", td_nanos(delta) as f64 / 1e9)
 }
 
-#[derive(Debug)]
+fn td_hours(delta: TimeDelta) -> f64 {
+    td_nanos(delta) as f64 / 3.6e12
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ConstantSchedule {
     pub var_type: String,
     pub value: Value,
@@ -100,34 +526,329 @@ impl VarSchedule for ConstantSchedule {
         self.value.clone()
     }
 
+    fn ceil_search(&self, _time: &DateTime<Utc>) -> Value {
+        self.value.clone()
+    }
+
+    fn nearest_search(&self, _time: &DateTime<Utc>) -> Value {
+        self.value.clone()
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.value.is_number()
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        Some(vec![self.value.clone()])
+    }
+
     fn floor_multi_search(&self, times: &[DateTime<Utc>]) -> Vec<Value> {
         vec![self.value.clone(); times.len()]
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PeriodicSchedule {
     pub var_type: String,
     pub start_point: DateTime<Utc>,
-    pub period: TimeDelta,
+    pub period: Period,
+    #[serde(serialize_with = "serialize_all_seconds")]
     pub times: Vec<TimeDelta>,
     pub values: Vec<Value>,
+    #[serde(rename = "default")]
     pub default_val: Value,
+    /// values older than this since their transition revert to default
+    #[serde(serialize_with = "serialize_opt_seconds")]
+    pub max_stale: Option<TimeDelta>,
+    /// restart each local day from a solar event instead of `start_point`
+    pub solar: Option<SolarAnchor>,
+    /// before the first point of a period, hold the previous period's last value
+    pub carry_in: bool,
+    /// the schedule expires here; from then on it serves default
+    pub end_point: Option<DateTime<Utc>>,
+    pub interpolation: InterpolationMode,
+    /// served where no point is in effect yet
+    pub pre_start: PreStartBehavior,
+    /// timezone whose calendar days, months and years a calendar period follows
+    pub timezone: Zone,
+}
+
+/// Length of a periodic schedule's cycle
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// serialized in seconds
+    Fixed(#[serde(serialize_with = "serialize_seconds")] TimeDelta),
+    /// calendar months; a start past the end of a shorter month clamps to its
+    /// last day (Jan 31 is followed by Feb 28 or 29, then Mar 31)
+    Months(u32),
+    Years(u32),
+    /// local days, each cycle starting at the same wall-clock time even
+    /// across daylight-saving changes
+    Days(u32),
+}
+
+impl Period {
+    fn months(&self) -> Option<u32> {
+        match self {
+            Period::Fixed(_) | Period::Days(_) => None,
+            Period::Months(months) => Some(*months),
+            Period::Years(years) => Some(years * 12),
+        }
+    }
+
+    /// lower bound on the length of any one cycle; for local days the
+    /// nominal length, which daylight-saving changes shorten or stretch
+    pub fn min_length(&self) -> TimeDelta {
+        match self.months() {
+            None | Some(0) => match self {
+                Period::Fixed(length) => *length,
+                Period::Days(days) => TimeDelta::days((*days).into()),
+                _ => TimeDelta::zero(),
+            },
+            // a year has at least 365 days, and a run of fewer than 12
+            // months at least one February and all other months of 30 days
+            Some(months) => {
+                let (years, months) = (months / 12, months % 12);
+                let rest = match months {
+                    0 => 0,
+                    months => 28 + 30 * (months - 1),
+                };
+                TimeDelta::days((365 * years + rest).into())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Period::Fixed(length) => write!(f, "{}h", length.num_milliseconds() as f64 / 3.6e6),
+            Period::Months(months) => write!(f, "{months} month(s)"),
+            Period::Years(years) => write!(f, "{years} year(s)"),
+            Period::Days(days) => write!(f, "{days} local day(s)"),
+        }
+    }
+}
+
+/// Parse a periodic schedule's ISO 8601 period; months and years make a
+/// calendar period, and can't be mixed with smaller units
+fn parse_period_iso8601(input: &str) -> Result<Period, ScheduleError> {
+    let raw = input
+        .parse::<iso8601_duration::Duration>()
+        .map_err(|e| ScheduleError::InvalidDuration(format!("{e:?}")))?;
+    if raw.year == 0.0 && raw.month == 0.0 {
+        return parse_duration_iso8601(input).map(Period::Fixed);
+    }
+    let fixed_part = raw.day != 0.0 || raw.hour != 0.0 || raw.minute != 0.0 || raw.second != 0.0;
+    if fixed_part || raw.year.fract() != 0.0 || raw.month.fract() != 0.0 {
+        return Err(ScheduleError::MixedCalendarPeriod(input.to_owned()));
+    }
+    match (raw.year as u32, raw.month as u32) {
+        (years, 0) => Ok(Period::Years(years)),
+        (years, months) => Ok(Period::Months(years * 12 + months)),
+    }
+}
+
+/// Daily solar event at a site that a T24 schedule's times are offsets from
+#[derive(Debug, Clone, Serialize)]
+pub struct SolarAnchor {
+    pub event: Solar,
+    #[serde(serialize_with = "serialize_site")]
+    pub site: Coordinates,
+    /// timezone whose calendar days the events are computed for
+    pub timezone: Zone,
+}
+
+fn serialize_site<S: Serializer>(site: &Coordinates, s: S) -> Result<S::Ok, S::Error> {
+    [site.lat(), site.lon()].serialize(s)
+}
+
+impl SolarAnchor {
+    /// the event on `time`'s local day; None if it doesn't occur (polar day or night)
+    pub fn event_on(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let date = self.timezone.local(time).date();
+        let event = match self.event {
+            Solar::Sunrise => SolarEvent::Sunrise,
+            Solar::Sunset => SolarEvent::Sunset,
+        };
+        SolarDay::new(self.site, date).event_time(event)
+    }
+
+    /// start of `time`'s local day
+    pub fn day_start(&self, time: &DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = self.timezone.local(time).date().and_time(NaiveTime::MIN);
+        self.timezone.instant(&midnight)
+    }
+}
+
+/// Assembles a [`PeriodicSchedule`] point by point instead of from parallel
+/// `times`/`values` arrays; points may be added in any order
+///
+/// ```
+/// use chrono::TimeDelta;
+/// use luz_optimon::{parse_datetime_iso8601, PeriodicSchedule, VarSchedule};
+///
+/// let start = parse_datetime_iso8601("2024-01-01T00:00:00Z").unwrap();
+/// let led = PeriodicSchedule::builder("intensity")
+///     .start(start)
+///     .period(TimeDelta::hours(24))
+///     .point(TimeDelta::hours(18), 0)
+///     .point(TimeDelta::hours(6), 100)
+///     .default(0)
+///     .build()
+///     .unwrap();
+/// let noon = parse_datetime_iso8601("2024-01-02T12:00:00Z").unwrap();
+/// assert_eq!(led.floor_search(&noon), 100);
+///
+/// // two points at one time, and a point past the end of the cycle
+/// let builder = PeriodicSchedule::builder("intensity")
+///     .start(start)
+///     .period(TimeDelta::hours(24));
+/// let twice = builder.clone().point(TimeDelta::hours(6), 1).point(TimeDelta::hours(6), 2);
+/// assert!(twice.build().is_err());
+/// assert!(builder.point(TimeDelta::hours(25), 1).build().is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeriodicScheduleBuilder {
+    var_type: String,
+    start: Option<DateTime<Utc>>,
+    period: Option<Period>,
+    points: Vec<(TimeDelta, Value)>,
+    default_val: Value,
+    end: Option<DateTime<Utc>>,
+}
+
+impl PeriodicScheduleBuilder {
+    pub fn new(var_type: impl Into<String>) -> Self {
+        Self {
+            var_type: var_type.into(),
+            start: None,
+            period: None,
+            points: Vec::new(),
+            default_val: Value::Null,
+            end: None,
+        }
+    }
+
+    /// start of the first cycle
+    pub fn start(mut self, start: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// fixed cycle length
+    pub fn period(mut self, period: TimeDelta) -> Self {
+        self.period = Some(Period::Fixed(period));
+        self
+    }
+
+    /// calendar cycle length, e.g. `Period::Months(1)`
+    pub fn calendar_period(mut self, period: Period) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    /// `value` takes effect `offset` into each cycle
+    pub fn point(mut self, offset: TimeDelta, value: impl Into<Value>) -> Self {
+        self.points.push((offset, value.into()));
+        self
+    }
+
+    /// served outside the schedule; null unless set
+    pub fn default(mut self, value: impl Into<Value>) -> Self {
+        self.default_val = value.into();
+        self
+    }
+
+    /// the schedule expires here
+    pub fn end(mut self, end: DateTime<Utc>) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Sort the points and check them as [`PeriodicSchedule::new`] does
+    pub fn build(mut self) -> Result<PeriodicSchedule, ScheduleError> {
+        let start = self.start.ok_or(ScheduleError::BuilderMissing("a start"))?;
+        let period = self
+            .period
+            .ok_or(ScheduleError::BuilderMissing("a period"))?;
+        self.points.sort_by_key(|(offset, _)| *offset);
+        if let Some(pair) = self.points.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(ScheduleError::DuplicateTime {
+                time: Period::Fixed(pair[0].0).to_string(),
+            });
+        }
+        let (times, values) = self.points.into_iter().unzip();
+        PeriodicSchedule::from_deltas(
+            self.var_type,
+            start,
+            period,
+            times,
+            values,
+            self.default_val,
+            self.end,
+        )
+    }
 }
 
 impl PeriodicSchedule {
     pub fn new(
         var_type: String,
         start_date: DateTime<Utc>,
-        period: f64,
+        period: Period,
         times: Vec<f64>,
         values: Vec<Value>,
         default_val: Value,
-    ) -> Result<Self, String> {
-        let period = hours_to_td(period)
-            .map_err(|e| format!("Failed to parse period for periodic schedule: {}", e))?;
-        let times = convert_times(times)
-            .map_err(|e| format!("Failed to parse time(s) for periodic schedule: {}", e))?;
+        end_date: Option<DateTime<Utc>>,
+    ) -> Result<Self, ScheduleError> {
+        let times = convert_times(times).map_err(|e| ScheduleError::InvalidTimes(Box::new(e)))?;
+        Self::from_deltas(
+            var_type,
+            start_date,
+            period,
+            times,
+            values,
+            default_val,
+            end_date,
+        )
+    }
+
+    /// [`PeriodicSchedule::new`] with times already as offsets into the cycle
+    fn from_deltas(
+        var_type: String,
+        start_date: DateTime<Utc>,
+        period: Period,
+        times: Vec<TimeDelta>,
+        values: Vec<Value>,
+        default_val: Value,
+        end_date: Option<DateTime<Utc>>,
+    ) -> Result<Self, ScheduleError> {
+        if end_date.is_some_and(|end| end <= start_date) {
+            return Err(ScheduleError::EndBeforeStart);
+        }
+        if period.min_length() <= TimeDelta::zero() {
+            return Err(ScheduleError::NonPositivePeriod);
+        }
+        if times.is_empty() {
+            return Err(ScheduleError::EmptyTimes);
+        }
+        if times.len() != values.len() {
+            return Err(ScheduleError::LengthMismatch {
+                times: times.len(),
+                values: values.len(),
+            });
+        }
+        // searches binary-search the times
+        if let Some(index) = times.windows(2).position(|pair| pair[1] <= pair[0]) {
+            return Err(ScheduleError::UnsortedTimes { index: index + 1 });
+        }
+        if let Some(time) = times.iter().find(|t| **t >= period.min_length()) {
+            return Err(ScheduleError::TimeOutsideCycle {
+                time: Period::Fixed(*time).to_string(),
+                period: period.to_string(),
+            });
+        }
         Ok(Self {
             var_type,
             start_point: start_date,
@@ -135,190 +856,2533 @@ impl PeriodicSchedule {
             times,
             values,
             default_val,
+            max_stale: None,
+            solar: None,
+            carry_in: false,
+            end_point: end_date,
+            interpolation: InterpolationMode::Floor,
+            pre_start: PreStartBehavior::Default,
+            timezone: Zone::default(),
         })
     }
 
-    pub fn most_recent_start(&self, time: &DateTime<Utc>) -> DateTime<Utc> {
-        let elapsed = *time - self.start_point;
-        let approx_n = elapsed.num_seconds() / self.period.num_seconds();
-        let most_recent_start = self.start_point + self.period * approx_n as i32;
-        // ! May need to add / subtract by a period until
-        //   most_recent_start is the maximum solution to S = start + k*period
-        //   where S still comes before theoretic datetime
-        debug_assert!(most_recent_start <= *time); // can fail if fetching time before start
-        most_recent_start
+    fn to_entry(&self, header: ScheduleHeader, start_date: DateTime<Utc>) -> PeriodicEntry {
+        let period = match self.period {
+            Period::Fixed(length) => PeriodSpec::Hours(td_hours(length)),
+            // local days only come from T24 schedules
+            Period::Days(days) => PeriodSpec::Hours(24.0 * f64::from(days)),
+            Period::Months(months) => PeriodSpec::Duration(format!("P{months}M")),
+            Period::Years(years) => PeriodSpec::Duration(format!("P{years}Y")),
+        };
+        // T24 schedules start from the file's StartOffset instead
+        let offset = self.start_point - start_date;
+        let offset_time =
+            (!period.is_t24() && !offset.is_zero()).then(|| OffsetSpec::Hours(td_hours(offset)));
+        PeriodicEntry {
+            header,
+            period,
+            times: self.times.iter().map(|t| td_hours(*t)).collect(),
+            values: self.values.clone(),
+            offset_time,
+            max_stale: self.max_stale.map(iso8601_duration),
+            monotonic: None,
+            solar: self.solar.as_ref().map(|anchor| anchor.event),
+            carry_in: self.carry_in,
+            end_date: self.end_point.as_ref().map(iso8601_time),
+            interpolation: self.interpolation,
+            pre_start: self.pre_start,
+        }
     }
 
-    pub fn fetch_schedule_point(&self, time: &DateTime<Utc>) -> TimeDelta {
-        let most_recent_start = self.most_recent_start(time);
-        let schedule_time = *time - most_recent_start;
-        debug_assert!(schedule_time < self.period);
-        schedule_time
+    pub fn builder(var_type: impl Into<String>) -> PeriodicScheduleBuilder {
+        PeriodicScheduleBuilder::new(var_type)
     }
-}
 
-impl VarSchedule for PeriodicSchedule {
-    fn var_type(&self) -> String {
-        self.var_type.to_owned()
+    /// start of cycle `k`, counted from `start_point` (which starts cycle 0)
+    fn nth_start(&self, k: i64) -> Option<DateTime<Utc>> {
+        let local = self.timezone.local(&self.start_point);
+        let local = match self.period {
+            Period::Fixed(period) => {
+                return Some(self.start_point + td_from_nanos(k as i128 * td_nanos(period))?);
+            }
+            Period::Days(days) => {
+                let offset = Days::new(k.unsigned_abs() * days as u64);
+                match k {
+                    0.. => local.checked_add_days(offset)?,
+                    _ => local.checked_sub_days(offset)?,
+                }
+            }
+            // computed from the start each time, so a clamped day doesn't stick
+            Period::Months(_) | Period::Years(_) => {
+                let months = self.period.months()? as u64;
+                let offset = Months::new(u32::try_from(k.unsigned_abs() * months).ok()?);
+                match k {
+                    0.. => local.checked_add_months(offset)?,
+                    _ => local.checked_sub_months(offset)?,
+                }
+            }
+        };
+        Some(self.timezone.instant(&local))
     }
 
-    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
-        // todo: add upper bound here too, if provided
-        if *time > self.start_point {
-            let schedule_time = self.fetch_schedule_point(time);
-            match self.times.binary_search(&schedule_time) {
-                Ok(index) => self.values[index].clone(),
-                Err(index) => {
-                    if index == 0 && schedule_time < self.times[0] {
-                        self.default_val.clone()
-                    } else {
-                        self.values[index - 1].clone()
-                    }
-                }
+    /// index of the cycle `time` falls in; negative before `start_point`
+    fn cycle_index(&self, time: &DateTime<Utc>) -> i64 {
+        let k = match self.period {
+            Period::Fixed(period) => {
+                // exact integer nanoseconds, so boundaries don't drift with float error
+                let elapsed = td_nanos(*time - self.start_point);
+                return elapsed.div_euclid(td_nanos(period)) as i64;
+            }
+            Period::Days(days) => {
+                let date = |t: &DateTime<Utc>| self.timezone.local(t).date();
+                let elapsed = (date(time) - date(&self.start_point)).num_days();
+                elapsed.div_euclid(days as i64)
             }
-        } else {
-            self.default_val.clone()
+            Period::Months(_) | Period::Years(_) => {
+                let month_index = |t: &DateTime<Utc>| {
+                    let local = self.timezone.local(t);
+                    local.year() as i64 * 12 + local.month0() as i64
+                };
+                let elapsed = month_index(time) - month_index(&self.start_point);
+                elapsed.div_euclid(self.period.months().unwrap_or(1) as i64)
+            }
+        };
+        // counting days or months ignores the time of day, so the estimate
+        // can be one late
+        match self.nth_start(k) {
+            Some(start) if start > *time => k - 1,
+            _ => k,
         }
     }
-}
 
-/// Map from variable name to its schedule
-pub type ScheduleMap = HashMap<String, Schedule>;
-pub type NamespaceMap = HashMap<String, ScheduleMap>;
-
-pub fn parse_schedules(file: LunaLuz) -> Result<(ScheduleMap, NamespaceMap), String> {
-    let start_date = parse_datetime_iso8601(&file.info.start_date)
-        .map_err(|e| format!("Invalid start date format: {e}"))?;
+    /// start of the cycle `n` cycles after the one starting at `start`
+    fn shift_start(&self, start: DateTime<Utc>, n: i64) -> Option<DateTime<Utc>> {
+        self.nth_start(self.cycle_index(&start) + n)
+    }
 
-    // timezone included to ensure T24 schedules start on the expected day
-    // even in periods when UTC time is on a different day than local time
-    let timezone = TimeDelta::hours(file.info.timezone);
+    /// latest cycle start at or before `time`; None if `time` is before
+    /// `start_point`
+    pub fn most_recent_start(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if *time < self.start_point {
+            return None;
+        }
+        self.nth_start(self.cycle_index(time))
+    }
 
-    let start_offset = parse_duration_iso8601(&file.info.start_offset)?;
+    /// time since the most recent period start; None before `start_point`
+    pub fn fetch_schedule_point(&self, time: &DateTime<Utc>) -> Option<TimeDelta> {
+        let most_recent_start = self.most_recent_start(time)?;
+        Some(*time - most_recent_start)
+    }
 
-    let t24_start_point = start_date + timezone;
-    let t24_start_point = midnight(&t24_start_point) + start_offset - timezone;
+    /// index of the point in effect at `time` and the instant it took effect;
+    /// None before the start or before the first point of a period
+    pub fn floor_point(&self, time: &DateTime<Utc>) -> Option<(usize, DateTime<Utc>)> {
+        if *time < self.start_point {
+            return None;
+        }
+        let schedule_time = match &self.solar {
+            Some(solar) => *time - solar.event_on(time)?,
+            None => self.fetch_schedule_point(time)?,
+        };
+        let index = match self.times.binary_search(&schedule_time) {
+            Ok(index) => index,
+            Err(0) if self.carry_in => return self.carried_point(time, schedule_time),
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let since = *time - schedule_time + self.times[index];
+        Some((index, since))
+    }
 
-    // NOTE: This logic should be repeated down below for var schedules in extensions
-    let mut schedules: ScheduleMap = HashMap::new();
-    for (name, schedule) in file.variable_schedules.into_iter() {
-        schedule.is_valid()?;
+    /// value before the start or before a period's first point, where no
+    /// point is in effect
+    fn pre_start_value(&self) -> Value {
+        match (self.pre_start, self.values.last()) {
+            (PreStartBehavior::HoldLast, Some(last)) => last.clone(),
+            _ => self.default_val.clone(),
+        }
+    }
 
-        let var_type = schedule.variable_type().to_owned();
-        let spec = file
-            .var_type_specs
-            .get(&var_type)
-            .ok_or_else(|| format!("Unknown variable type for {name}"))?;
+    /// the previous period's last point, held into the current period
+    /// until its first point; None if there is no previous period
+    fn carried_point(
+        &self,
+        time: &DateTime<Utc>,
+        schedule_time: TimeDelta,
+    ) -> Option<(usize, DateTime<Utc>)> {
+        let index = self.times.len().checked_sub(1)?;
+        let previous_start = match &self.solar {
+            Some(solar) => solar.event_on(&(*time - TimeDelta::days(1)))?,
+            None => self.shift_start(*time - schedule_time, -1)?,
+        };
+        let since = previous_start + self.times[index];
+        (since > self.start_point).then_some((index, since))
+    }
 
-        let schedule: Schedule = match schedule {
-            ScheduleEntry::Default { .. } => {
-                let value = spec.default.clone();
-                Schedule::Constant(ConstantSchedule::new(var_type, value))
+    /// index of the first point at or after `time` and the instant it takes
+    /// effect, wrapping into the next period; None if the schedule has no
+    /// points or expires first
+    pub fn ceil_point(&self, time: &DateTime<Utc>) -> Option<(usize, DateTime<Utc>)> {
+        let first = *self.times.first()?;
+        let (anchor, next_anchor) = match &self.solar {
+            Some(solar) => {
+                let time = (*time).max(self.start_point);
+                let next_day = solar.event_on(&(time + TimeDelta::days(1)));
+                (solar.event_on(&time), next_day)
             }
-            ScheduleEntry::Constant { value, .. } => {
-                Schedule::Constant(ConstantSchedule::new(var_type, value))
+            None => {
+                let start = self.most_recent_start(time).unwrap_or(self.start_point);
+                (Some(start), self.shift_start(start, 1))
             }
-            ScheduleEntry::Periodic {
-                period,
-                times,
-                values,
-                offset_time,
-                ..
-            } => {
-                let start_point = if period == 24.0 {
-                    t24_start_point
-                } else if let Some(offset_time) = offset_time {
-                    start_date
-                        + hours_to_td(offset_time).map_err(|e| {
-                            format!("Failed to parse offset time for '{name}': {}", e)
-                        })?
-                } else {
-                    start_date
+        };
+        let next = anchor
+            .and_then(|anchor| {
+                let index = match self.times.binary_search(&(*time - anchor)) {
+                    Ok(index) | Err(index) => index,
                 };
-                let default_value = spec.default.clone();
+                self.times.get(index).map(|t| (index, anchor + *t))
+            })
+            .filter(|(_, at)| self.solar.is_none() || *at > self.start_point)
+            .or_else(|| Some((0, next_anchor? + first)))?;
+        match self.end_point {
+            Some(end) if next.1 >= end => None,
+            _ => Some(next),
+        }
+    }
 
-                Schedule::Periodic(PeriodicSchedule::new(
-                    var_type,
-                    start_point,
-                    period,
-                    times,
-                    values,
-                    default_value,
-                )?)
+    /// the value ramped linearly between the points around `time`, wrapping
+    /// from a period's last point to the next period's first; non-numeric
+    /// values are held as with floor search. None where default applies
+    fn linear_value(&self, time: &DateTime<Utc>) -> Option<Value> {
+        if *time < self.start_point || self.times.is_empty() {
+            return None;
+        }
+        let schedule_time = self.fetch_schedule_point(time)?;
+        let last = self.times.len() - 1;
+        let cycle_start = *time - schedule_time;
+        let (before, after) = match self.times.binary_search(&schedule_time) {
+            Ok(index) => return Some(self.values[index].clone()),
+            Err(0) => {
+                let previous = cycle_start - self.shift_start(cycle_start, -1)?;
+                ((last, self.times[last] - previous), (0, self.times[0]))
             }
+            Err(index) if index > last => {
+                let length = self.shift_start(cycle_start, 1)? - cycle_start;
+                ((last, self.times[last]), (0, self.times[0] + length))
+            }
+            Err(index) => (
+                (index - 1, self.times[index - 1]),
+                (index, self.times[index]),
+            ),
         };
 
-        schedules.insert(name, schedule);
+        // no previous point to ramp from in the first period
+        if cycle_start + before.1 < self.start_point {
+            return None;
+        }
+        let (Some(from), Some(to)) = (
+            self.values[before.0].as_f64(),
+            self.values[after.0].as_f64(),
+        ) else {
+            return Some(self.values[before.0].clone());
+        };
+        let fraction =
+            td_nanos(schedule_time - before.1) as f64 / td_nanos(after.1 - before.1) as f64;
+        Some(Value::from(from + (to - from) * fraction))
     }
+}
 
-    // ------ handle extension namespaces ---------
+impl VarSchedule for PeriodicSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
 
-    let mut ext_namespaces: NamespaceMap = HashMap::new();
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        if self.end_point.is_some_and(|end| *time >= end) {
+            return self.default_val.clone();
+        }
+        let floor = self.floor_point(time);
+        // stale before ramping too, so a linear schedule can't outlive its points
+        if let Some((_, since)) = floor {
+            if self.max_stale.is_some_and(|max| *time - since > max) {
+                return self.default_val.clone();
+            }
+        }
+        if self.interpolation == InterpolationMode::Linear {
+            return self.interp_search(time);
+        }
+        match floor {
+            Some((index, _)) => self.values[index].clone(),
+            None => self.pre_start_value(),
+        }
+    }
+
+    fn ceil_search(&self, time: &DateTime<Utc>) -> Value {
+        if *time < self.start_point {
+            return self.pre_start_value();
+        }
+        match self.ceil_point(time) {
+            Some((index, _)) => self.values[index].clone(),
+            None => self.default_val.clone(),
+        }
+    }
+
+    fn nearest_search(&self, time: &DateTime<Utc>) -> Value {
+        if self.end_point.is_some_and(|end| *time >= end) {
+            return self.default_val.clone();
+        }
+        if *time < self.start_point {
+            return self.pre_start_value();
+        }
+        // before a period's first point, the previous period's last counts too
+        let floor = self.floor_point(time).or_else(|| {
+            let schedule_time = match &self.solar {
+                Some(solar) => *time - solar.event_on(time)?,
+                None => self.fetch_schedule_point(time)?,
+            };
+            self.carried_point(time, schedule_time)
+        });
+        let index = match (floor, self.ceil_point(time)) {
+            (Some((floor, since)), Some((ceil, at))) => {
+                if *time - since <= at - *time {
+                    floor
+                } else {
+                    ceil
+                }
+            }
+            (Some((index, _)), None) | (None, Some((index, _))) => index,
+            (None, None) => return self.default_val.clone(),
+        };
+        self.values[index].clone()
+    }
+
+    fn interp_search(&self, time: &DateTime<Utc>) -> Value {
+        if self.end_point.is_some_and(|end| *time >= end) {
+            return self.default_val.clone();
+        }
+        self.linear_value(time)
+            .unwrap_or_else(|| self.pre_start_value())
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.default_val.is_number() && self.values.iter().all(Value::is_number)
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        let mut domain = vec![self.default_val.clone()];
+        for value in self.values.iter() {
+            if !domain.contains(value) {
+                domain.push(value.clone());
+            }
+        }
+        Some(domain)
+    }
+
+    fn cycle(&self) -> Option<(DateTime<Utc>, TimeDelta)> {
+        // calendar periods vary in length; report the first cycle's
+        let next_start = self.nth_start(1)?;
+        Some((self.start_point, next_start - self.start_point))
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        // nothing changes after the schedule expires
+        let in_range =
+            |t: &DateTime<Utc>| t > start && t < end && self.end_point.is_none_or(|e| *t <= e);
+        // a set, since cycle starts and stale instants can coincide with points
+        let mut points: BTreeSet<DateTime<Utc>> = std::iter::once(self.start_point)
+            .chain(self.end_point)
+            .filter(in_range)
+            .collect();
+        let from = (*start).max(self.start_point);
+        let cycles_end = match self.end_point {
+            Some(end_point) => (*end).min(end_point),
+            None => *end,
+        };
+
+        // begin a period early so stale or carried values from it are covered
+        let mut cycle_start = match &self.solar {
+            Some(solar) => solar.day_start(&(from - TimeDelta::days(1))),
+            None => {
+                let start = self.most_recent_start(&from).unwrap_or(self.start_point);
+                self.shift_start(start, -1).unwrap_or(start)
+            }
+        };
+        while cycle_start < cycles_end && points.len() <= limit {
+            let anchor = match &self.solar {
+                Some(solar) => solar.event_on(&cycle_start),
+                None => Some(cycle_start),
+            };
+            let times = self.times.iter().filter_map(|t| Some(anchor? + *t));
+            let cycle_points = times
+                .flat_map(|time| std::iter::once(time).chain(self.max_stale.map(|max| time + max)));
+            points.extend(
+                std::iter::once(cycle_start)
+                    .chain(cycle_points)
+                    .filter(in_range),
+            );
+            cycle_start = match &self.solar {
+                Some(solar) => solar.day_start(&(cycle_start + TimeDelta::days(1))),
+                None => match self.shift_start(cycle_start, 1) {
+                    Some(next_start) => next_start,
+                    None => break,
+                },
+            };
+        }
+
+        points.into_iter().take(limit + 1).collect()
+    }
+}
+
+/// Schedule computed from a formula over other variables' values
+#[derive(Debug, Clone, Serialize)]
+pub struct DerivedSchedule {
+    pub var_type: String,
+    /// source text of `tree`
+    pub formula: String,
+    #[serde(skip)]
+    pub tree: Node,
+    /// referenced variables, resolved at load time; exported by name
+    #[serde(serialize_with = "serialize_input_names")]
+    pub inputs: Vec<(String, Schedule)>,
+    #[serde(rename = "default")]
+    pub default_val: Value,
+}
+
+fn serialize_input_names<S: Serializer>(
+    inputs: &[(String, Schedule)],
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.collect_seq(inputs.iter().map(|(name, _)| name))
+}
+
+impl DerivedSchedule {
+    pub fn new(
+        var_type: String,
+        formula: String,
+        tree: Node,
+        inputs: Vec<(String, Schedule)>,
+        default_val: Value,
+    ) -> Self {
+        Self {
+            var_type,
+            formula,
+            tree,
+            inputs,
+            default_val,
+        }
+    }
+
+    fn evaluate(&self, time: &DateTime<Utc>) -> Option<Value> {
+        let mut context = HashMapContext::<DefaultNumericTypes>::new();
+        for (name, schedule) in self.inputs.iter() {
+            let value = match schedule.floor_search(time) {
+                Value::Number(n) if n.is_i64() => EvalValue::Int(n.as_i64()?),
+                Value::Number(n) => EvalValue::Float(n.as_f64()?),
+                _ => return None,
+            };
+            context.set_value(name.clone(), value).ok()?;
+        }
+
+        match self.tree.eval_with_context(&context).ok()? {
+            EvalValue::Int(i) => Some(Value::from(i)),
+            EvalValue::Float(f) => serde_json::Number::from_f64(f).map(Value::Number),
+            _ => None,
+        }
+    }
+}
+
+impl VarSchedule for DerivedSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        // formula errors (e.g. division by zero) fall back to the default
+        self.evaluate(time)
+            .unwrap_or_else(|| self.default_val.clone())
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.default_val.is_number()
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        None
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let mut points: Vec<DateTime<Utc>> = self
+            .inputs
+            .iter()
+            .flat_map(|(_, input)| input.change_points(start, end, limit))
+            .collect();
+        points.sort();
+        points.dedup();
+        points.truncate(limit + 1);
+        points
+    }
+}
+
+/// Variable type given to extension extras, which have no spec
+pub const ADMIN_VAR_TYPE: &str = "ADMIN";
+
+/// Map from variable name to its schedule
+pub type ScheduleMap = HashMap<String, Schedule>;
+pub type NamespaceMap = HashMap<String, Namespace>;
+
+/// Extension namespace: its schedules and the specs private to it
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    pub schedules: ScheduleMap,
+    /// types declared by the namespace, on top of the global specs
+    pub specs: HashMap<String, VariableTypeSpec>,
+}
+
+/// Schedule repeating a periodic source's transitions after a delay
+#[derive(Debug, Clone, Serialize)]
+pub struct FollowSchedule {
+    pub var_type: String,
+    /// name of the followed variable
+    pub target: String,
+    pub source: PeriodicSchedule,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub delay: TimeDelta,
+    pub map: Option<HashMap<String, Value>>,
+    #[serde(rename = "default")]
+    pub default_val: Value,
+}
+
+impl FollowSchedule {
+    pub fn new(
+        var_type: String,
+        target: String,
+        source: PeriodicSchedule,
+        delay: TimeDelta,
+        map: Option<HashMap<String, Value>>,
+        default_val: Value,
+    ) -> Self {
+        Self {
+            var_type,
+            target,
+            source,
+            delay,
+            map,
+            default_val,
+        }
+    }
+
+    fn map_value(&self, value: Value) -> Value {
+        let Some(map) = &self.map else {
+            return value;
+        };
+        let key = match &value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        map.get(&key)
+            .cloned()
+            .unwrap_or_else(|| self.default_val.clone())
+    }
+}
+
+impl VarSchedule for FollowSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        let delayed = *time - self.delay;
+        // before the source's first transition there is nothing to follow
+        match self.source.floor_point(&delayed) {
+            Some(_) => self.map_value(self.source.floor_search(&delayed)),
+            None => self.default_val.clone(),
+        }
+    }
+
+    fn ceil_search(&self, time: &DateTime<Utc>) -> Value {
+        let delayed = *time - self.delay;
+        match self.source.ceil_point(&delayed) {
+            Some(_) => self.map_value(self.source.ceil_search(&delayed)),
+            None => self.default_val.clone(),
+        }
+    }
+
+    fn nearest_search(&self, time: &DateTime<Utc>) -> Value {
+        let delayed = *time - self.delay;
+        self.map_value(self.source.nearest_search(&delayed))
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.domain()
+            .is_some_and(|values| values.iter().all(Value::is_number))
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        let mut domain = vec![self.default_val.clone()];
+        for value in self.source.domain()? {
+            let value = self.map_value(value);
+            if !domain.contains(&value) {
+                domain.push(value);
+            }
+        }
+        Some(domain)
+    }
+
+    fn cycle(&self) -> Option<(DateTime<Utc>, TimeDelta)> {
+        let (start_point, period) = self.source.cycle()?;
+        Some((start_point + self.delay, period))
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let (start, end) = (*start - self.delay, *end - self.delay);
+        let points = self.source.change_points(&start, &end, limit);
+        points.into_iter().map(|t| t + self.delay).collect()
+    }
+}
+
+/// Non-repeating schedule holding each breakpoint's value until the next
+#[derive(Debug, Clone, Serialize)]
+pub struct AbsoluteSchedule {
+    pub var_type: String,
+    /// sorted by time
+    pub breakpoints: Vec<(DateTime<Utc>, Value)>,
+    #[serde(rename = "default")]
+    pub default_val: Value,
+}
+
+impl VarSchedule for AbsoluteSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        // before the first breakpoint there is nothing to hold
+        match self.breakpoints.partition_point(|(t, _)| t <= time) {
+            0 => self.default_val.clone(),
+            index => self.breakpoints[index - 1].1.clone(),
+        }
+    }
+
+    fn ceil_search(&self, time: &DateTime<Utc>) -> Value {
+        // after the last breakpoint there is nothing to come; hold it
+        let index = self.breakpoints.partition_point(|(t, _)| t < time);
+        match self.breakpoints.get(index) {
+            Some((_, value)) => value.clone(),
+            None => self.floor_search(time),
+        }
+    }
+
+    fn nearest_search(&self, time: &DateTime<Utc>) -> Value {
+        let index = self.breakpoints.partition_point(|(t, _)| t <= time);
+        let before = index.checked_sub(1).map(|i| &self.breakpoints[i]);
+        match (before, self.breakpoints.get(index)) {
+            (Some((floor, value)), Some((ceil, next))) => {
+                if *time - *floor <= *ceil - *time {
+                    value.clone()
+                } else {
+                    next.clone()
+                }
+            }
+            (Some((_, value)), None) | (None, Some((_, value))) => value.clone(),
+            (None, None) => self.default_val.clone(),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.default_val.is_number() && self.breakpoints.iter().all(|(_, v)| v.is_number())
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        let mut domain = vec![self.default_val.clone()];
+        for (_, value) in self.breakpoints.iter() {
+            if !domain.contains(value) {
+                domain.push(value.clone());
+            }
+        }
+        Some(domain)
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        self.breakpoints
+            .iter()
+            .map(|(t, _)| *t)
+            .filter(|t| t > start && t < end)
+            .take(limit + 1)
+            .collect()
+    }
+
+    fn next_change(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // breakpoints can lie arbitrarily far ahead, so skip the horizon
+        let current = self.floor_search(time);
+        let index = self.breakpoints.partition_point(|(t, _)| t <= time);
+        self.breakpoints[index..]
+            .iter()
+            .find(|(_, value)| *value != current)
+            .map(|(t, _)| *t)
+    }
+}
+
+/// One-off events, each holding its value for a fixed duration; the default
+/// applies whenever no event covers the time
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSchedule {
+    pub var_type: String,
+    /// `(start, end, value)` sorted by start; where events overlap the later wins
+    pub events: Vec<(DateTime<Utc>, DateTime<Utc>, Value)>,
+    #[serde(rename = "default")]
+    pub default_val: Value,
+}
+
+impl EventSchedule {
+    /// value of the latest-starting event covering `time`, if any
+    fn active(&self, time: &DateTime<Utc>) -> Option<&Value> {
+        let started = self.events.partition_point(|(start, _, _)| start <= time);
+        self.events[..started]
+            .iter()
+            .rev()
+            .find(|(_, end, _)| end > time)
+            .map(|(_, _, value)| value)
+    }
+}
+
+impl VarSchedule for EventSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        self.active(time).unwrap_or(&self.default_val).clone()
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.default_val.is_number() && self.events.iter().all(|(_, _, v)| v.is_number())
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        let mut domain = vec![self.default_val.clone()];
+        for (_, _, value) in self.events.iter() {
+            if !domain.contains(value) {
+                domain.push(value.clone());
+            }
+        }
+        Some(domain)
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let points: BTreeSet<DateTime<Utc>> = self
+            .events
+            .iter()
+            .flat_map(|(from, to, _)| [*from, *to])
+            .filter(|t| t > start && t < end)
+            .collect();
+        points.into_iter().take(limit + 1).collect()
+    }
+}
+
+/// Piecewise-linear profile through absolute `(time, value)` anchors, held at
+/// the first value before the first anchor and the last after the last
+#[derive(Debug, Clone, Serialize)]
+pub struct RampSchedule {
+    pub var_type: String,
+    /// never empty; strictly increasing in time
+    pub anchors: Vec<(DateTime<Utc>, f64)>,
+}
+
+impl VarSchedule for RampSchedule {
+    fn var_type(&self) -> String {
+        self.var_type.to_owned()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        let index = self.anchors.partition_point(|(t, _)| t <= time);
+        let value = match (index.checked_sub(1), self.anchors.get(index)) {
+            (Some(i), Some((t1, v1))) => {
+                let (t0, v0) = self.anchors[i];
+                let span = (*t1 - t0).num_milliseconds() as f64;
+                let frac = (*time - t0).num_milliseconds() as f64 / span;
+                v0 + (v1 - v0) * frac
+            }
+            (Some(i), None) => self.anchors[i].1,
+            (None, _) => self.anchors[0].1,
+        };
+        Value::from(value)
+    }
+
+    fn is_numeric(&self) -> bool {
+        true
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        None
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        self.anchors
+            .iter()
+            .map(|(t, _)| *t)
+            .filter(|t| t > start && t < end)
+            .take(limit + 1)
+            .collect()
+    }
+}
+
+/// Check a scheduled value against its type: Interval and Ratio values must
+/// be numbers within any declared Min and Max, and categorical values one of
+/// the declared categories
+fn check_value_type(spec: &VariableTypeSpec, value: &Value) -> Result<(), ScheduleError> {
+    match spec.var_type {
+        VarDataType::Interval | VarDataType::Ratio => {
+            let expected = match (spec.min, spec.max) {
+                (Some(min), Some(max)) => format!("a number from {min} to {max}"),
+                (Some(min), None) => format!("a number of at least {min}"),
+                (None, Some(max)) => format!("a number of at most {max}"),
+                (None, None) => "a number".to_string(),
+            };
+            match value.as_f64() {
+                Some(v)
+                    if spec.min.is_none_or(|min| v >= min)
+                        && spec.max.is_none_or(|max| v <= max) =>
+                {
+                    Ok(())
+                }
+                _ => Err(ScheduleError::ValueTypeMismatch {
+                    value: value.clone(),
+                    expected,
+                }),
+            }
+        }
+        VarDataType::Nominal | VarDataType::Ordinal => {
+            let Some(categories) = &spec.categories else {
+                return Ok(());
+            };
+            match value.as_str() {
+                Some(v) if categories.iter().any(|c| c == v) => Ok(()),
+                _ => Err(ScheduleError::ValueTypeMismatch {
+                    value: value.clone(),
+                    expected: format!("one of {categories:?}"),
+                }),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check the values of a Nominal or Ordinal variable against its declared
+/// categories, and of an Interval or Ratio variable against its declared
+/// bounds; other types, and types without either, pass
+fn check_values<'a>(
+    name: &str,
+    spec: &VariableTypeSpec,
+    values: impl IntoIterator<Item = &'a Value>,
+) -> Result<(), ScheduleError> {
+    let bounded = spec.min.is_some() || spec.max.is_some();
+    match spec.var_type {
+        VarDataType::Nominal | VarDataType::Ordinal => {}
+        VarDataType::Interval | VarDataType::Ratio if bounded => {}
+        _ => return Ok(()),
+    }
+    for value in values {
+        check_value_type(spec, value).map_err(|e| ScheduleError::InvalidValue {
+            name: name.to_owned(),
+            error: Box::new(e),
+        })?;
+    }
+    Ok(())
+}
+
+/// Linear unit conversion `value * scale + offset` of numeric values
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ValueTransform {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl ValueTransform {
+    /// non-numeric values pass through unchanged
+    pub fn apply(&self, value: Value) -> Value {
+        match value.as_f64() {
+            Some(v) => Value::from(v * self.scale + self.offset),
+            None => value,
+        }
+    }
+}
+
+/// Schedule whose numeric values are converted on the way out; derived and
+/// follow schedules referencing it see the untransformed values
+#[derive(Debug, Clone, Serialize)]
+pub struct TransformedSchedule {
+    pub inner: Box<Schedule>,
+    pub transform: ValueTransform,
+}
+
+impl VarSchedule for TransformedSchedule {
+    fn var_type(&self) -> String {
+        self.inner.var_type()
+    }
+
+    fn floor_search(&self, time: &DateTime<Utc>) -> Value {
+        self.transform.apply(self.inner.floor_search(time))
+    }
+
+    fn ceil_search(&self, time: &DateTime<Utc>) -> Value {
+        self.transform.apply(self.inner.ceil_search(time))
+    }
+
+    fn nearest_search(&self, time: &DateTime<Utc>) -> Value {
+        self.transform.apply(self.inner.nearest_search(time))
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.inner.is_numeric()
+    }
+
+    fn domain(&self) -> Option<Vec<Value>> {
+        let mut domain = Vec::new();
+        for value in self.inner.domain()? {
+            let value = self.transform.apply(value);
+            if !domain.contains(&value) {
+                domain.push(value);
+            }
+        }
+        Some(domain)
+    }
+
+    fn cycle(&self) -> Option<(DateTime<Utc>, TimeDelta)> {
+        self.inner.cycle()
+    }
+
+    fn change_points(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        self.inner.change_points(start, end, limit)
+    }
+
+    fn next_change(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.inner.next_change(time)
+    }
+}
+
+/// Piecewise-constant `(duration, value)` segments covering `(start, end)`;
+/// None if there are more than `limit` candidate change points, as with
+/// `value_changes`
+pub fn value_segments(
+    schedule: &Schedule,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    limit: usize,
+) -> Option<Vec<(TimeDelta, Value)>> {
+    let changes = schedule.change_points(start, end, limit);
+    if changes.len() > limit {
+        return None;
+    }
+
+    let bounds: Vec<DateTime<Utc>> = std::iter::once(*start)
+        .chain(changes)
+        .chain(std::iter::once(*end))
+        .collect();
+    let segments = bounds
+        .windows(2)
+        .map(|pair| {
+            // sampled mid-segment, away from boundaries that belong to either side
+            let mid = pair[0] + (pair[1] - pair[0]) / 2;
+            (pair[1] - pair[0], schedule.floor_search(&mid))
+        })
+        .collect();
+    Some(segments)
+}
+
+/// The value in effect at `start` followed by each `(instant, value)` in
+/// `(start, end)` where it changes; None if there are more than `limit`
+/// candidate change points
+pub fn value_changes(
+    schedule: &Schedule,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    limit: usize,
+) -> Option<Vec<(DateTime<Utc>, Value)>> {
+    let points = schedule.change_points(start, end, limit);
+    if points.len() > limit {
+        return None;
+    }
+
+    let mut changes = vec![(*start, schedule.floor_search(start))];
+    for time in points {
+        let value = schedule.floor_search(&time);
+        // change points may include instants where nothing changes
+        if changes.last().is_some_and(|(_, last)| *last != value) {
+            changes.push((time, value));
+        }
+    }
+    Some(changes)
+}
+
+/// Start points shared by every periodic schedule in a file
+struct StartPoints {
+    start_date: DateTime<Utc>,
+    t24: DateTime<Utc>,
+    timezone: Zone,
+    /// site location for solar schedules, if configured
+    site: Option<Coordinates>,
+}
+
+impl StartPoints {
+    fn new(info: &ScheduleInfo) -> Result<Self, ScheduleError> {
+        let start_date = parse_datetime_iso8601(&info.start_date)
+            .map_err(|e| ScheduleError::InvalidStartDate(Box::new(e)))?;
+
+        // timezone included to ensure T24 schedules start on the expected day
+        // even in periods when UTC time is on a different day than local time
+        let timezone = Zone::from_spec(&info.timezone)?;
+
+        let start_offset = parse_duration_iso8601(&info.start_offset)?;
+
+        let local_midnight = timezone.local(&start_date).date().and_time(NaiveTime::MIN);
+        let t24_start_point = timezone.instant(&(local_midnight + start_offset));
+
+        let site = match (info.latitude, info.longitude) {
+            (Some(lat), Some(lon)) => Some(Coordinates::new(lat, lon).ok_or(
+                ScheduleError::InvalidCoordinates {
+                    latitude: lat,
+                    longitude: lon,
+                },
+            )?),
+            _ => None,
+        };
+
+        Ok(Self {
+            start_date,
+            t24: t24_start_point,
+            timezone,
+            site,
+        })
+    }
+}
+
+pub fn parse_schedules(file: LunaLuz) -> Result<(ScheduleMap, NamespaceMap), ScheduleError> {
+    let starts = StartPoints::new(&file.info)?;
+
+    let events = build_events(file.event_schedules, &file.var_type_specs)?;
+    let schedules = build_schedules(
+        file.variable_schedules,
+        &file.var_type_specs,
+        &starts,
+        events,
+    )?;
+
+    // ------ handle extension namespaces ---------
+
+    let mut ext_namespaces: NamespaceMap = HashMap::new();
 
     for (ext_name, namespace) in file.extensions.into_iter() {
-        let mut ext_schedules: ScheduleMap = HashMap::new();
-
-        // handle variable schedules
-        for (name, schedule) in namespace.variable_schedules.into_iter() {
-            schedule.is_valid()?;
-
-            let var_type = schedule.variable_type().to_owned();
-            let spec = file
-                .var_type_specs
-                .get(&var_type)
-                .ok_or_else(|| format!("Unknown variable type for {name}"))?;
-
-            let schedule: Schedule = match schedule {
-                ScheduleEntry::Default { .. } => {
-                    let value = spec.default.clone();
-                    Schedule::Constant(ConstantSchedule::new(var_type, value))
+        let mut specs = file.var_type_specs.clone();
+        specs.extend(namespace.var_type_specs.clone());
+        let mut ext_schedules = build_schedules(
+            namespace.variable_schedules,
+            &specs,
+            &starts,
+            HashMap::new(),
+        )?;
+
+        // handle extras as const admin variables
+        for (name, value) in namespace.extra.into_iter() {
+            let schedule = Schedule::Constant(ConstantSchedule {
+                var_type: ADMIN_VAR_TYPE.to_string(),
+                value,
+            });
+            ext_schedules.insert(name, schedule);
+        }
+
+        let namespace = Namespace {
+            schedules: ext_schedules,
+            specs: namespace.var_type_specs,
+        };
+        ext_namespaces.insert(ext_name, namespace);
+    }
+
+    Ok((schedules, ext_namespaces))
+}
+
+/// Resolve a single entry `name` the way `parse_schedules` would, against the
+/// other schedules already built; returns `schedules` with `name` replaced
+pub fn replace_schedule(
+    info: &ScheduleInfo,
+    specs: &HashMap<String, VariableTypeSpec>,
+    schedules: &ScheduleMap,
+    name: &str,
+    entry: ScheduleEntry,
+) -> Result<ScheduleMap, ScheduleError> {
+    let starts = StartPoints::new(info)?;
+    // unwrapped so the entry's references resolve to untransformed schedules,
+    // as they do on load
+    let mut transforms = Vec::new();
+    let mut others = ScheduleMap::new();
+    for (other, schedule) in schedules.iter().filter(|(other, _)| *other != name) {
+        if let Schedule::Transformed(transformed) = schedule {
+            transforms.push((other.clone(), transformed.transform));
+        }
+        others.insert(other.clone(), schedule.raw().clone());
+    }
+
+    let entries = HashMap::from([(name.to_string(), entry)]);
+    let mut schedules = build_schedules(entries, specs, &starts, others)?;
+    apply_transforms(&mut schedules, transforms);
+    Ok(schedules)
+}
+
+/// Resolve the EventSchedules section
+fn build_events(
+    entries: HashMap<String, EventEntry>,
+    specs: &HashMap<String, VariableTypeSpec>,
+) -> Result<ScheduleMap, ScheduleError> {
+    let mut schedules = HashMap::new();
+    for (name, entry) in entries {
+        let spec =
+            specs
+                .get(&entry.variable_type)
+                .ok_or_else(|| ScheduleError::UnknownVariableType {
+                    name: name.clone(),
+                    var_type: entry.variable_type.clone(),
+                })?;
+
+        let mut events = Vec::new();
+        for (i, event) in entry.events.into_iter().enumerate() {
+            let invalid = |e| ScheduleError::InvalidEvent {
+                name: name.clone(),
+                index: i,
+                error: Box::new(e),
+            };
+            let start = parse_datetime_iso8601(&event.time).map_err(invalid)?;
+            let duration = parse_duration_iso8601(&event.duration).map_err(invalid)?;
+            if duration <= TimeDelta::zero() {
+                return Err(invalid(ScheduleError::NonPositiveDuration));
+            }
+            // equal starts are allowed; the later-defined event wins
+            if events.last().is_some_and(|(last, _, _)| *last > start) {
+                return Err(ScheduleError::EventOrder { name, index: i });
+            }
+            check_value_type(spec, &event.value).map_err(invalid)?;
+            events.push((start, start + duration, event.value));
+        }
+
+        let schedule = EventSchedule {
+            var_type: entry.variable_type,
+            events,
+            default_val: spec.default.clone(),
+        };
+        schedules.insert(name, Schedule::Event(schedule));
+    }
+    Ok(schedules)
+}
+
+/// Resolve one namespace's variable schedules on top of `schedules` already
+/// built; derived schedules are resolved last since they reference others
+fn build_schedules(
+    entries: HashMap<String, ScheduleEntry>,
+    specs: &HashMap<String, VariableTypeSpec>,
+    starts: &StartPoints,
+    mut schedules: ScheduleMap,
+) -> Result<ScheduleMap, ScheduleError> {
+    let mut derived: HashMap<String, PendingDerived> = HashMap::new();
+    let mut follows = Vec::new();
+    let mut transforms = Vec::new();
+
+    for (name, schedule) in entries.into_iter() {
+        if schedules.contains_key(&name) {
+            return Err(ScheduleError::DuplicateVariable { name });
+        }
+        schedule
+            .is_valid()
+            .map_err(|reason| ScheduleError::InvalidEntry {
+                name: name.clone(),
+                reason,
+            })?;
+
+        if let ScheduleEntry::Periodic(entry) = &schedule {
+            if let Some(warning) = entry.mixed_styles() {
+                tracing::warn!("schedule '{name}': {warning}");
+            }
+        }
+
+        if let Some((scale, offset)) = schedule.transform() {
+            transforms.push((name.clone(), ValueTransform { scale, offset }));
+        }
+
+        let var_type = schedule.variable_type().to_owned();
+        let spec = specs
+            .get(&var_type)
+            .ok_or_else(|| ScheduleError::UnknownVariableType {
+                name: name.clone(),
+                var_type: var_type.clone(),
+            })?;
+
+        let schedule: Schedule = match schedule {
+            ScheduleEntry::Default(_) => {
+                let value = spec.default.clone();
+                Schedule::Constant(ConstantSchedule::new(var_type, value))
+            }
+            ScheduleEntry::Constant(ConstantEntry { value, .. }) => {
+                check_values(&name, spec, [&value])?;
+                Schedule::Constant(ConstantSchedule::new(var_type, value))
+            }
+            ScheduleEntry::Periodic(PeriodicEntry {
+                period,
+                times,
+                values,
+                offset_time,
+                max_stale,
+                solar,
+                carry_in,
+                end_date,
+                interpolation,
+                pre_start,
+                ..
+            }) => {
+                let start_point = if period.is_t24() {
+                    starts.t24
+                } else if let Some(offset_time) = offset_time {
+                    let offset = match offset_time {
+                        OffsetSpec::Hours(hours) => hours_to_td(hours),
+                        OffsetSpec::Duration(duration) => parse_duration_iso8601(&duration),
+                    };
+                    starts.start_date + offset.map_err(|e| e.in_field(&name, "offset time"))?
+                } else {
+                    starts.start_date
+                };
+                let default_value = spec.default.clone();
+                let max_stale = max_stale
+                    .map(|d| parse_duration_iso8601(&d))
+                    .transpose()
+                    .map_err(|e| e.in_field(&name, "max stale"))?;
+                let solar = match solar {
+                    Some(event) => {
+                        let site = starts.site.ok_or_else(|| ScheduleError::MissingField {
+                            name: name.clone(),
+                            field: "Info Latitude and Longitude".to_string(),
+                        })?;
+                        Some(SolarAnchor {
+                            event,
+                            site,
+                            timezone: starts.timezone,
+                        })
+                    }
+                    None => None,
+                };
+                if interpolation == InterpolationMode::Linear
+                    && !matches!(spec.var_type, VarDataType::Interval | VarDataType::Ratio)
+                {
+                    return Err(ScheduleError::InterpolationType { name });
                 }
-                ScheduleEntry::Constant { value, .. } => {
-                    Schedule::Constant(ConstantSchedule::new(var_type, value))
+                let end_date = end_date
+                    .map(|d| parse_datetime_iso8601(&d))
+                    .transpose()
+                    .map_err(|e| e.in_field(&name, "end date"))?;
+                check_values(&name, spec, &values)?;
+                let period = match period {
+                    // keep local midnight through daylight-saving changes
+                    _ if period.is_t24() && !starts.timezone.is_fixed() => Ok(Period::Days(1)),
+                    PeriodSpec::Hours(hours) => hours_to_td(hours).map(Period::Fixed),
+                    PeriodSpec::Duration(duration) => parse_period_iso8601(&duration),
                 }
-                ScheduleEntry::Periodic {
-                    period,
-                    times,
-                    values,
-                    offset_time,
-                    ..
-                } => {
-                    let start_point = if period == 24.0 {
-                        t24_start_point
-                    } else if let Some(offset_time) = offset_time {
-                        start_date
-                            + hours_to_td(offset_time).map_err(|e| {
-                                format!("Failed to parse offset time for '{name}': {}", e)
-                            })?
-                    } else {
-                        start_date
-                    };
-                    let default_value = spec.default.clone();
+                .map_err(|e| e.in_field(&name, "period"))?;
 
-                    Schedule::Periodic(PeriodicSchedule::new(
+                Schedule::Periodic(PeriodicSchedule {
+                    max_stale,
+                    solar,
+                    carry_in,
+                    interpolation,
+                    pre_start,
+                    timezone: starts.timezone,
+                    ..PeriodicSchedule::new(
                         var_type,
                         start_point,
                         period,
                         times,
                         values,
                         default_value,
-                    )?)
+                        end_date,
+                    )
+                    .map_err(|e| ScheduleError::InvalidPeriodic {
+                        name: name.clone(),
+                        error: Box::new(e),
+                    })?
+                })
+            }
+            ScheduleEntry::Absolute(AbsoluteEntry { breakpoints, .. }) => {
+                let mut parsed = Vec::new();
+                for (i, breakpoint) in breakpoints.into_iter().enumerate() {
+                    let invalid = |e| ScheduleError::InvalidBreakpoint {
+                        name: name.clone(),
+                        index: i,
+                        error: Box::new(e),
+                    };
+                    let time = parse_datetime_iso8601(&breakpoint.time).map_err(invalid)?;
+                    if parsed.last().is_some_and(|(last, _)| *last >= time) {
+                        return Err(ScheduleError::BreakpointOrder { name, index: i });
+                    }
+                    check_value_type(spec, &breakpoint.value).map_err(invalid)?;
+                    parsed.push((time, breakpoint.value));
                 }
-            };
-            ext_schedules.insert(name, schedule);
+                Schedule::Absolute(AbsoluteSchedule {
+                    var_type,
+                    breakpoints: parsed,
+                    default_val: spec.default.clone(),
+                })
+            }
+            ScheduleEntry::Ramp(RampEntry { times, values, .. }) => {
+                if !matches!(spec.var_type, VarDataType::Interval | VarDataType::Ratio) {
+                    return Err(ScheduleError::InterpolationType { name });
+                }
+                let invalid = |reason: String| ScheduleError::InvalidEntry {
+                    name: name.clone(),
+                    reason,
+                };
+                if times.is_empty() {
+                    return Err(invalid("Times must not be empty".to_string()));
+                }
+                if times.len() != values.len() {
+                    return Err(invalid(format!(
+                        "{} Times but {} Values",
+                        times.len(),
+                        values.len()
+                    )));
+                }
+                let mut anchors: Vec<(DateTime<Utc>, f64)> = Vec::new();
+                for (i, (time, value)) in times.iter().zip(values).enumerate() {
+                    let time =
+                        parse_datetime_iso8601(time).map_err(|e| e.in_field(&name, "times"))?;
+                    if anchors.last().is_some_and(|(last, _)| *last >= time) {
+                        return Err(invalid(format!(
+                            "Times must be strictly increasing (index {i})"
+                        )));
+                    }
+                    anchors.push((time, value));
+                }
+                Schedule::Ramp(RampSchedule { var_type, anchors })
+            }
+            ScheduleEntry::Derived(DerivedEntry { formula, .. }) => {
+                let tree = build_operator_tree::<DefaultNumericTypes>(&formula).map_err(|e| {
+                    ScheduleError::InvalidFormula {
+                        name: name.clone(),
+                        message: e.to_string(),
+                    }
+                })?;
+                let pending = PendingDerived {
+                    var_type,
+                    formula,
+                    tree,
+                    default_val: spec.default.clone(),
+                };
+                derived.insert(name, pending);
+                continue;
+            }
+            ScheduleEntry::Follow(FollowEntry {
+                source, delay, map, ..
+            }) => {
+                let delay =
+                    parse_duration_iso8601(&delay).map_err(|e| e.in_field(&name, "delay"))?;
+                let pending = PendingFollow {
+                    name,
+                    var_type,
+                    source,
+                    delay,
+                    map,
+                    default_val: spec.default.clone(),
+                };
+                follows.push(pending);
+                continue;
+            }
+        };
+
+        schedules.insert(name, schedule);
+    }
+
+    // follow sources must be periodic, so follows can't form cycles;
+    // they are resolved before derived schedules, which may reference them
+    let following: HashSet<String> = follows.iter().map(|f| f.name.clone()).collect();
+    for follow in follows {
+        let PendingFollow {
+            name,
+            var_type,
+            source,
+            delay,
+            map,
+            default_val,
+        } = follow;
+        // another follow may not be inserted yet, so it is checked by name
+        let periodic = match schedules.get(&source) {
+            _ if following.contains(&source) => {
+                return Err(ScheduleError::FollowNotPeriodic {
+                    name,
+                    target: source,
+                })
+            }
+            Some(Schedule::Periodic(periodic)) => periodic.clone(),
+            Some(_) => {
+                return Err(ScheduleError::FollowNotPeriodic {
+                    name,
+                    target: source,
+                })
+            }
+            None => {
+                return Err(ScheduleError::UnknownVariable {
+                    name,
+                    target: source,
+                    relation: "followed by",
+                })
+            }
+        };
+        let schedule = FollowSchedule::new(var_type, source, periodic, delay, map, default_val);
+        schedules.insert(name, Schedule::Follow(schedule));
+    }
+
+    let names: Vec<String> = derived.keys().cloned().collect();
+    for name in names {
+        resolve_derived(&name, &mut derived, &mut schedules, &mut Vec::new())?;
+    }
+
+    // applied last so references above resolve to the untransformed schedules
+    apply_transforms(&mut schedules, transforms);
+
+    Ok(schedules)
+}
+
+fn apply_transforms(schedules: &mut ScheduleMap, transforms: Vec<(String, ValueTransform)>) {
+    for (name, transform) in transforms {
+        if let Some(inner) = schedules.remove(&name) {
+            let inner = Box::new(inner);
+            let schedule = Schedule::Transformed(TransformedSchedule { inner, transform });
+            schedules.insert(name, schedule);
         }
+    }
+}
 
-        // handle extras as const admin variables
-        for (name, value) in namespace.extra.into_iter() {
-            let schedule = Schedule::Constant(ConstantSchedule {
-                var_type: "ADMIN".to_string(),
-                value,
+struct PendingFollow {
+    name: String,
+    var_type: String,
+    source: String,
+    delay: TimeDelta,
+    map: Option<HashMap<String, Value>>,
+    default_val: Value,
+}
+
+struct PendingDerived {
+    var_type: String,
+    formula: String,
+    tree: Node,
+    default_val: Value,
+}
+
+/// Depth-first resolution of a derived schedule and its references;
+/// `stack` holds the chain currently being resolved, to detect cycles
+fn resolve_derived(
+    name: &str,
+    pending: &mut HashMap<String, PendingDerived>,
+    schedules: &mut ScheduleMap,
+    stack: &mut Vec<String>,
+) -> Result<(), ScheduleError> {
+    if stack.iter().any(|n| n == name) {
+        stack.push(name.to_owned());
+        return Err(ScheduleError::CyclicReference(stack.clone()));
+    }
+    let Some(derived) = pending.remove(name) else {
+        // already resolved (or never derived)
+        return Ok(());
+    };
+
+    stack.push(name.to_owned());
+    let references: Vec<String> = derived
+        .tree
+        .iter_variable_identifiers()
+        .map(str::to_owned)
+        .collect();
+
+    let mut inputs = Vec::new();
+    for reference in references {
+        if inputs.iter().any(|(n, _)| n == &reference) {
+            continue;
+        }
+        // referenced derived schedules are resolved first; a reference
+        // already on the stack is reported as a cycle
+        if pending.contains_key(&reference) || stack.contains(&reference) {
+            resolve_derived(&reference, pending, schedules, stack)?;
+        }
+        let input = schedules
+            .get(&reference)
+            .ok_or_else(|| ScheduleError::UnknownVariable {
+                name: name.to_owned(),
+                target: reference.clone(),
+                relation: "referenced by",
+            })?;
+        if !input.is_numeric() {
+            return Err(ScheduleError::NotNumeric {
+                name: name.to_owned(),
+                target: reference,
             });
-            ext_schedules.insert(name, schedule);
         }
+        inputs.push((reference, input.clone()));
+    }
+    stack.pop();
+
+    let schedule = DerivedSchedule::new(
+        derived.var_type,
+        derived.formula,
+        derived.tree,
+        inputs,
+        derived.default_val,
+    );
+    schedules.insert(name.to_owned(), Schedule::Derived(schedule));
+    Ok(())
+}
 
-        ext_namespaces.insert(ext_name, ext_schedules);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(input: &str) -> DateTime<Utc> {
+        parse_datetime_iso8601(input).unwrap()
     }
 
-    Ok((schedules, ext_namespaces))
+    /// Parse a file starting 2024-01-01 in `timezone`, with `VariableSchedules`
+    /// `schedules`, an "intensity" Ratio type and a "color" Nominal type
+    fn file(timezone: &str, schedules: &str) -> LunaLuz {
+        let text = format!(
+            r#"{{
+                "Info": {{
+                    "Version": "1.0", "Timezone": {timezone}, "StartDate": "2024-01-01T00:00:00Z",
+                    "StartOffset": "PT0H", "ExperimentName": "Demo", "CabinetID": "C1",
+                    "User": "tester", "Description": "sample", "Latitude": 40.7,
+                    "Longitude": -74.0, "Parents": {{"Primary": "none", "Secondary": []}}
+                }},
+                "VarTypeSpecs": {{
+                    "intensity": {{"VariableType": "Ratio", "DefaultValue": 0, "Description": "light"}},
+                    "color": {{"VariableType": "Nominal", "DefaultValue": "off",
+                               "Description": "color", "Categories": ["off", "red", "blue"]}}
+                }},
+                "VariableSchedules": {schedules}
+            }}"#
+        );
+        crate::lunaluz_deserialization::parse_schedule_file(
+            &text,
+            crate::lunaluz_deserialization::FileFormat::Json,
+            true,
+        )
+        .unwrap()
+    }
+
+    fn load(timezone: &str, schedules: &str) -> Result<ScheduleMap, ScheduleError> {
+        parse_schedules(file(timezone, schedules)).map(|(schedules, _)| schedules)
+    }
+
+    /// 24h cycle from 2024-01-01: 1 at 00:00, 2 at 12:00; default 0
+    fn periodic() -> PeriodicSchedule {
+        PeriodicSchedule::new(
+            "intensity".to_string(),
+            time("2024-01-01T00:00:00Z"),
+            Period::Fixed(TimeDelta::hours(24)),
+            vec![0.0, 12.0],
+            vec![Value::from(1), Value::from(2)],
+            Value::from(0),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn floor_search_at_cycle_boundaries() {
+        let schedule = periodic();
+        assert_eq!(schedule.floor_search(&time("2024-01-01T00:00:00Z")), 1);
+        assert_eq!(schedule.floor_search(&time("2024-01-02T00:00:00Z")), 1);
+        assert_eq!(schedule.floor_search(&time("2024-01-01T12:00:00Z")), 2);
+    }
+
+    #[test]
+    fn floor_search_just_before_a_boundary() {
+        let schedule = periodic();
+        let boundary = time("2024-01-02T00:00:00Z");
+        let before = boundary - TimeDelta::microseconds(1);
+        assert_eq!(schedule.floor_search(&before), 2);
+        assert_eq!(schedule.floor_point(&before).unwrap().0, 1);
+    }
+
+    #[test]
+    fn floor_search_before_start() {
+        let schedule = periodic();
+        let before = time("2024-01-01T00:00:00Z") - TimeDelta::microseconds(1);
+        assert_eq!(schedule.floor_point(&before), None);
+        assert_eq!(schedule.floor_search(&before), 0);
+    }
+
+    #[test]
+    fn ceil_search_takes_the_next_point() {
+        let schedule = periodic();
+        let second = TimeDelta::seconds(1);
+        assert_eq!(schedule.ceil_search(&time("2024-01-01T00:00:00Z")), 1);
+        assert_eq!(
+            schedule.ceil_search(&(time("2024-01-01T00:00:00Z") + second)),
+            2
+        );
+        // wraps into the next period after the last point
+        assert_eq!(
+            schedule.ceil_search(&(time("2024-01-01T12:00:00Z") + second)),
+            1
+        );
+    }
+
+    #[test]
+    fn nearest_search_ties_go_to_the_earlier_point() {
+        let schedule = periodic();
+        let second = TimeDelta::seconds(1);
+        let midway = time("2024-01-01T06:00:00Z");
+        assert_eq!(schedule.nearest_search(&midway), 1);
+        assert_eq!(schedule.nearest_search(&(midway - second)), 1);
+        assert_eq!(schedule.nearest_search(&(midway + second)), 2);
+        // midway between 12:00 and the next period's 00:00
+        assert_eq!(schedule.nearest_search(&time("2024-01-01T18:00:00Z")), 2);
+        assert_eq!(schedule.nearest_search(&time("2024-01-01T18:00:01Z")), 1);
+    }
+
+    #[test]
+    fn every_search_mode_serves_the_pre_start_value() {
+        let mut schedule = periodic();
+        // just before the start, where ceil and nearest would find 00:00's point
+        let before = time("2024-01-01T00:00:00Z") - TimeDelta::seconds(1);
+        assert_eq!(schedule.ceil_search(&before), 0);
+        assert_eq!(schedule.nearest_search(&before), 0);
+        assert_eq!(schedule.interp_search(&before), 0);
+
+        schedule.pre_start = PreStartBehavior::HoldLast;
+        assert_eq!(schedule.floor_search(&before), 2);
+        assert_eq!(schedule.ceil_search(&before), 2);
+        assert_eq!(schedule.nearest_search(&before), 2);
+    }
+
+    /// `PeriodicSchedule::new` over a 24h period, rendered as its error
+    fn invalid(times: Vec<f64>, values: Vec<i32>) -> String {
+        PeriodicSchedule::new(
+            "intensity".to_string(),
+            time("2024-01-01T00:00:00Z"),
+            Period::Fixed(TimeDelta::hours(24)),
+            times,
+            values.into_iter().map(Value::from).collect(),
+            Value::from(0),
+            None,
+        )
+        .unwrap_err()
+        .to_string()
+    }
+
+    #[test]
+    fn periodic_times_must_be_sorted() {
+        assert_eq!(
+            invalid(vec![0.0, 12.0, 6.0], vec![1, 2, 3]),
+            "Times of periodic schedule must be strictly increasing (index 2)"
+        );
+        assert_eq!(
+            invalid(vec![0.0, 6.0, 6.0], vec![1, 2, 3]),
+            "Times of periodic schedule must be strictly increasing (index 2)"
+        );
+    }
+
+    #[test]
+    fn periodic_times_and_values_must_match() {
+        assert_eq!(
+            invalid(vec![0.0, 12.0], vec![1]),
+            "Periodic schedule has 2 times but 1 values"
+        );
+    }
+
+    #[test]
+    fn periodic_times_must_not_be_empty() {
+        assert_eq!(
+            invalid(Vec::new(), Vec::new()),
+            "Periodic schedule must have at least one time"
+        );
+    }
+
+    #[test]
+    fn periodic_times_must_be_within_the_period() {
+        for hours in [24.0, 30.0] {
+            assert_eq!(
+                invalid(vec![0.0, hours], vec![1, 2]),
+                format!("time {hours}h is not within every cycle of 24h")
+            );
+        }
+    }
+
+    fn ending(end: &str) -> Result<PeriodicSchedule, ScheduleError> {
+        PeriodicSchedule::new(
+            "intensity".to_string(),
+            time("2024-01-01T00:00:00Z"),
+            Period::Fixed(TimeDelta::hours(24)),
+            vec![0.0, 12.0],
+            vec![Value::from(1), Value::from(2)],
+            Value::from(0),
+            Some(time(end)),
+        )
+    }
+
+    #[test]
+    fn end_date_reverts_to_default() {
+        let schedule = ending("2024-01-03T06:00:00Z").unwrap();
+        let before_start = time("2023-12-31T18:00:00Z");
+        assert_eq!(schedule.floor_search(&before_start), 0);
+        assert_eq!(schedule.floor_search(&time("2024-01-02T13:00:00Z")), 2);
+        let end = time("2024-01-03T06:00:00Z");
+        assert_eq!(schedule.floor_search(&(end - TimeDelta::seconds(1))), 1);
+        assert_eq!(schedule.floor_search(&end), 0);
+        assert_eq!(schedule.floor_search(&time("2024-01-03T12:00:00Z")), 0);
+        assert_eq!(schedule.floor_search(&time("2025-01-01T00:00:00Z")), 0);
+    }
+
+    #[test]
+    fn end_date_must_follow_the_start() {
+        for end in ["2024-01-01T00:00:00Z", "2023-06-01T00:00:00Z"] {
+            let error = ending(end).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "EndDate of periodic schedule must be after its start"
+            );
+        }
+    }
+
+    #[test]
+    fn max_stale_reverts_held_values() {
+        let schedule = PeriodicSchedule {
+            max_stale: Some(TimeDelta::hours(2)),
+            ..periodic()
+        };
+        let point = time("2024-01-02T12:00:00Z");
+        let window = point + TimeDelta::hours(2);
+        assert_eq!(schedule.floor_search(&window), 2);
+        assert_eq!(schedule.floor_search(&(window + TimeDelta::seconds(1))), 0);
+    }
+
+    #[test]
+    fn max_stale_applies_to_linear_schedules() {
+        let schedule = PeriodicSchedule {
+            max_stale: Some(TimeDelta::hours(2)),
+            interpolation: InterpolationMode::Linear,
+            ..periodic()
+        };
+        // ramping from 1 at 00:00 towards 2 at 12:00
+        let within = time("2024-01-02T01:30:00Z");
+        assert_eq!(schedule.floor_search(&within), 1.125);
+        let beyond = time("2024-01-02T02:00:01Z");
+        assert_eq!(schedule.floor_search(&beyond), 0);
+    }
+
+    #[test]
+    fn whole_second_hours_convert_exactly() {
+        // 0.1 * 3600 and 24.1 * 3600 are both a little over in floating point
+        assert_eq!(hours_to_td(0.1).unwrap(), TimeDelta::seconds(360));
+        assert_eq!(hours_to_td(24.1).unwrap(), TimeDelta::seconds(86_760));
+        assert_eq!(hours_to_td(6.0).unwrap(), TimeDelta::hours(6));
+    }
+
+    #[test]
+    fn points_stay_on_the_second_across_many_periods() {
+        let start = time("2024-01-01T00:00:00Z");
+        let schedule = PeriodicSchedule::new(
+            "intensity".to_string(),
+            start,
+            Period::Fixed(hours_to_td(24.1).unwrap()),
+            vec![0.0, 6.0],
+            vec![Value::from(1), Value::from(2)],
+            Value::from(0),
+            None,
+        )
+        .unwrap();
+        let hundredth = start + TimeDelta::seconds(86_760) * 100;
+        let probe = hundredth + TimeDelta::hours(7);
+        assert_eq!(schedule.most_recent_start(&probe), Some(hundredth));
+        let point = hundredth + TimeDelta::hours(6);
+        assert_eq!(schedule.floor_point(&probe), Some((1, point)));
+        assert_eq!(
+            schedule.floor_search(&(point - TimeDelta::nanoseconds(1))),
+            1
+        );
+        assert_eq!(schedule.floor_search(&point), 2);
+    }
+
+    fn calendar(start: &str, period: &str) -> PeriodicSchedule {
+        PeriodicSchedule::new(
+            "intensity".to_string(),
+            time(start),
+            parse_period_iso8601(period).unwrap(),
+            vec![0.0],
+            vec![Value::from(1)],
+            Value::from(0),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn monthly_period_clamps_to_short_months() {
+        let schedule = calendar("2024-01-31T00:00:00Z", "P1M");
+        assert_eq!(schedule.period, Period::Months(1));
+        assert_eq!(schedule.nth_start(1), Some(time("2024-02-29T00:00:00Z")));
+        assert_eq!(schedule.nth_start(2), Some(time("2024-03-31T00:00:00Z")));
+        assert_eq!(schedule.nth_start(3), Some(time("2024-04-30T00:00:00Z")));
+        let april = time("2024-04-30T12:00:00Z");
+        assert_eq!(schedule.most_recent_start(&april), schedule.nth_start(3));
+    }
+
+    #[test]
+    fn yearly_period_from_a_leap_day() {
+        let schedule = calendar("2024-02-29T00:00:00Z", "P1Y");
+        assert_eq!(schedule.period, Period::Years(1));
+        assert_eq!(schedule.nth_start(1), Some(time("2025-02-28T00:00:00Z")));
+        assert_eq!(schedule.nth_start(4), Some(time("2028-02-29T00:00:00Z")));
+        let march = time("2025-03-01T00:00:00Z");
+        assert_eq!(schedule.most_recent_start(&march), schedule.nth_start(1));
+    }
+
+    #[test]
+    fn linear_value_includes_the_first_cycle_start() {
+        let schedule = PeriodicSchedule {
+            interpolation: InterpolationMode::Linear,
+            ..periodic()
+        };
+        assert_eq!(schedule.interp_search(&time("2024-01-01T00:00:00Z")), 1);
+        assert_eq!(schedule.interp_search(&time("2024-01-01T06:00:00Z")), 1.5);
+        let before = time("2024-01-01T00:00:00Z") - TimeDelta::microseconds(1);
+        assert_eq!(schedule.interp_search(&before), 0);
+    }
+
+    fn linear() -> PeriodicSchedule {
+        PeriodicSchedule {
+            interpolation: InterpolationMode::Linear,
+            ..periodic()
+        }
+    }
+
+    #[test]
+    fn linear_interpolation_at_the_midpoint() {
+        let schedule = linear();
+        assert_eq!(schedule.interp_search(&time("2024-01-02T06:00:00Z")), 1.5);
+        assert_eq!(schedule.interp_search(&time("2024-01-02T03:00:00Z")), 1.25);
+        assert_eq!(schedule.interp_search(&time("2024-01-02T12:00:00Z")), 2);
+    }
+
+    #[test]
+    fn linear_interpolation_wraps_into_the_next_cycle() {
+        let schedule = linear();
+        // from 2 at 12:00 back down to 1 at the next cycle's 00:00
+        assert_eq!(schedule.interp_search(&time("2024-01-01T18:00:00Z")), 1.5);
+        assert_eq!(schedule.interp_search(&time("2024-01-01T21:00:00Z")), 1.25);
+        assert_eq!(schedule.interp_search(&time("2024-01-02T00:00:00Z")), 1);
+    }
+
+    #[test]
+    fn linear_interpolation_holds_non_numeric_values() {
+        let schedule = PeriodicSchedule {
+            values: vec![Value::from("red"), Value::from("blue")],
+            ..linear()
+        };
+        assert_eq!(
+            schedule.interp_search(&time("2024-01-01T18:00:00Z")),
+            "blue"
+        );
+    }
+
+    fn daily(timezone: &str) -> PeriodicSchedule {
+        let schedules = load(
+            timezone,
+            r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0, 6],
+                        "Values": [1, 2]}}"#,
+        )
+        .unwrap();
+        match &schedules["led"] {
+            Schedule::Periodic(periodic) => periodic.clone(),
+            schedule => panic!("expected a periodic schedule, got {}", schedule.kind()),
+        }
+    }
+
+    #[test]
+    fn t24_with_a_fixed_offset_starts_at_its_midnight() {
+        let schedule = daily("-5");
+        assert_eq!(schedule.period, Period::Fixed(TimeDelta::hours(24)));
+        let summer = time("2024-07-01T12:00:00Z");
+        assert_eq!(
+            schedule.most_recent_start(&summer),
+            Some(time("2024-07-01T05:00:00Z"))
+        );
+    }
+
+    #[cfg(feature = "tz")]
+    fn daily_new_york() -> PeriodicSchedule {
+        daily(r#""America/New_York""#)
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn t24_keeps_local_midnight_across_spring_forward() {
+        let schedule = daily_new_york();
+        // EST (UTC-5) before 2024-03-10, EDT (UTC-4) after
+        let saturday = time("2024-03-09T12:00:00Z");
+        let monday = time("2024-03-11T12:00:00Z");
+        assert_eq!(
+            schedule.most_recent_start(&saturday),
+            Some(time("2024-03-09T05:00:00Z"))
+        );
+        assert_eq!(
+            schedule.most_recent_start(&monday),
+            Some(time("2024-03-11T04:00:00Z"))
+        );
+
+        // Times are elapsed hours from the day's anchor, midnight EST
+        let six = time("2024-03-10T11:00:00Z");
+        assert_eq!(schedule.floor_search(&six), 2);
+        assert_eq!(
+            schedule.floor_search(&(six - TimeDelta::microseconds(1))),
+            1
+        );
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn t24_keeps_local_midnight_across_fall_back() {
+        let schedule = daily_new_york();
+        // EDT (UTC-4) before 2024-11-03, EST (UTC-5) after
+        let saturday = time("2024-11-02T12:00:00Z");
+        let monday = time("2024-11-04T12:00:00Z");
+        assert_eq!(
+            schedule.most_recent_start(&saturday),
+            Some(time("2024-11-02T04:00:00Z"))
+        );
+        assert_eq!(
+            schedule.most_recent_start(&monday),
+            Some(time("2024-11-04T05:00:00Z"))
+        );
+
+        // Times are elapsed hours from the day's anchor, midnight EDT
+        let six = time("2024-11-03T10:00:00Z");
+        assert_eq!(schedule.floor_search(&six), 2);
+        assert_eq!(
+            schedule.floor_search(&(six - TimeDelta::microseconds(1))),
+            1
+        );
+    }
+
+    #[test]
+    fn derived_schedule_sums_its_inputs() {
+        let schedules = load(
+            "0",
+            r#"{"red": {"VariableType": "intensity", "Period": 24, "Times": [0, 12],
+                        "Values": [1, 2]},
+                "blue": {"VariableType": "intensity", "Value": 10},
+                "total": {"VariableType": "intensity", "Formula": "red + blue"}}"#,
+        )
+        .unwrap();
+        let total = &schedules["total"];
+        assert_eq!(total.floor_search(&time("2024-01-01T06:00:00Z")), 11.0);
+        assert_eq!(total.floor_search(&time("2024-01-01T18:00:00Z")), 12.0);
+        assert_eq!(total.references(), ["red", "blue"]);
+    }
+
+    #[test]
+    fn derived_schedules_reject_cycles() {
+        let result = load(
+            "0",
+            r#"{"a": {"VariableType": "intensity", "Formula": "b + 1"},
+                "b": {"VariableType": "intensity", "Formula": "a * 2"}}"#,
+        );
+        match result {
+            Err(ScheduleError::CyclicReference(path)) => {
+                assert_eq!(path.first(), path.last());
+                assert!(path.contains(&"a".to_string()) && path.contains(&"b".to_string()));
+            }
+            other => panic!("expected a cyclic reference, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn derived_schedules_reject_non_numeric_inputs() {
+        let result = load(
+            "0",
+            r#"{"hue": {"VariableType": "color", "Value": "red"},
+                "bad": {"VariableType": "intensity", "Formula": "hue + 1"}}"#,
+        );
+        assert!(matches!(result, Err(ScheduleError::NotNumeric { .. })));
+    }
+
+    fn sample_times() -> impl Iterator<Item = DateTime<Utc>> {
+        let start = time("2023-12-31T00:00:00Z");
+        (0..200).map(move |i| start + TimeDelta::minutes(37 * i))
+    }
+
+    #[test]
+    fn builder_matches_the_parsed_entry() {
+        let schedules = load(
+            "0",
+            r#"{"led": {"VariableType": "intensity", "Period": 12, "Times": [0, 2.5, 8],
+                        "Values": [1, 2, 3]}}"#,
+        )
+        .unwrap();
+        let built = PeriodicSchedule::builder("intensity")
+            .start(time("2024-01-01T00:00:00Z"))
+            .period(TimeDelta::hours(12))
+            .point(TimeDelta::hours(8), 3)
+            .point(TimeDelta::zero(), 1)
+            .point(TimeDelta::minutes(150), 2)
+            .default(0)
+            .build()
+            .unwrap();
+
+        let parsed = &schedules["led"];
+        let export = |s: &Schedule| serde_json::to_value(s).unwrap();
+        assert_eq!(export(parsed), export(&Schedule::Periodic(built.clone())));
+        for t in sample_times() {
+            assert_eq!(parsed.floor_search(&t), built.floor_search(&t), "{t}");
+        }
+    }
+
+    #[test]
+    fn builder_rejects_duplicate_times() {
+        let result = PeriodicSchedule::builder("intensity")
+            .start(time("2024-01-01T00:00:00Z"))
+            .period(TimeDelta::hours(24))
+            .point(TimeDelta::hours(6), 1)
+            .point(TimeDelta::hours(6), 2)
+            .build();
+        assert!(matches!(result, Err(ScheduleError::DuplicateTime { .. })));
+    }
+
+    #[test]
+    fn builder_rejects_points_past_the_period() {
+        let result = PeriodicSchedule::builder("intensity")
+            .start(time("2024-01-01T00:00:00Z"))
+            .period(TimeDelta::hours(24))
+            .point(TimeDelta::hours(24), 1)
+            .build();
+        assert!(matches!(
+            result,
+            Err(ScheduleError::TimeOutsideCycle { .. })
+        ));
+    }
+
+    #[test]
+    fn builder_requires_a_start_and_a_period() {
+        let result = PeriodicSchedule::builder("intensity")
+            .period(TimeDelta::hours(24))
+            .point(TimeDelta::zero(), 1)
+            .build();
+        assert!(matches!(
+            result,
+            Err(ScheduleError::BuilderMissing("a start"))
+        ));
+        let result = PeriodicSchedule::builder("intensity")
+            .start(time("2024-01-01T00:00:00Z"))
+            .point(TimeDelta::zero(), 1)
+            .build();
+        assert!(matches!(
+            result,
+            Err(ScheduleError::BuilderMissing("a period"))
+        ));
+    }
+
+    #[test]
+    fn to_entry_round_trips_every_variant() {
+        let schedules = r#"{
+            "hue": {"VariableType": "color", "Value": "red"},
+            "dflt": {"VariableType": "intensity", "ScheduleType": "default"},
+            "led": {"VariableType": "intensity", "Period": 2.5, "Times": [0, 1.25],
+                    "Values": [100, 0], "OffsetTime": "PT30M", "MaxStale": "PT1H",
+                    "CarryIn": true, "EndDate": "2024-01-03T00:00:00Z"},
+            "daily": {"VariableType": "intensity", "Period": 24, "Times": [6, 18],
+                      "Values": [1, 2], "PreStart": "hold_last", "Interpolation": "linear"},
+            "dawn": {"VariableType": "intensity", "Period": 24, "Times": [0, 1],
+                     "Values": [3, 4], "Solar": "sunrise"},
+            "monthly": {"VariableType": "color", "Period": "P1M", "Times": [0, 240],
+                        "Values": ["red", "blue"]},
+            "lux": {"VariableType": "intensity", "Formula": "led * 2 + daily", "Scale": 10,
+                    "Offset": 1},
+            "echo": {"VariableType": "intensity", "Source": "led", "Delay": "PT15M",
+                     "Map": {"100": 1}},
+            "steps": {"VariableType": "intensity", "Breakpoints": [
+                {"Time": "2024-01-01T10:00:00Z", "Value": 5},
+                {"Time": "2024-01-01T14:30:00.5Z", "Value": 7}]},
+            "ramp": {"VariableType": "intensity", "Times": ["2024-01-01T00:00:00Z",
+                     "2024-01-02T00:00:00Z"], "Values": [0, 50]}
+        }"#;
+        let mut file = file("-5", schedules);
+        let start = time("2024-01-01T00:00:00Z");
+        let (original, _) = parse_schedules(file.clone()).unwrap();
+        let kinds: BTreeSet<&str> = original.values().map(|s| s.kind()).collect();
+        // events have no entry; see event_schedules_have_no_entry
+        assert_eq!(kinds.len(), 6, "{kinds:?}");
+        assert!(matches!(original["lux"], Schedule::Transformed(_)));
+
+        let entries: HashMap<String, ScheduleEntry> = original
+            .iter()
+            .map(|(name, schedule)| (name.clone(), schedule.to_entry(start).unwrap()))
+            .collect();
+        let json = serde_json::to_string(&entries).unwrap();
+        file.variable_schedules = serde_json::from_str(&json).unwrap();
+        let (rebuilt, _) = parse_schedules(file).unwrap();
+
+        for (name, schedule) in &original {
+            let again = &rebuilt[name];
+            assert_eq!(schedule.kind(), again.kind(), "{name}");
+            for t in sample_times().step_by(3) {
+                assert_eq!(
+                    schedule.floor_search(&t),
+                    again.floor_search(&t),
+                    "{name} {t}"
+                );
+                assert_eq!(
+                    schedule.ceil_search(&t),
+                    again.ceil_search(&t),
+                    "{name} {t}"
+                );
+                assert_eq!(
+                    schedule.interp_search(&t),
+                    again.interp_search(&t),
+                    "{name} {t}"
+                );
+                assert_eq!(
+                    schedule.nearest_search(&t),
+                    again.nearest_search(&t),
+                    "{name} {t}"
+                );
+            }
+        }
+    }
+
+    /// a pulse of 1 from 02:00 to 04:00 with a 2 from 03:00 to 03:30 inside
+    /// it, then a 3 from 06:00 to 07:00
+    fn events() -> EventSchedule {
+        let event =
+            |start: &str, end: &str, value: i32| (time(start), time(end), Value::from(value));
+        EventSchedule {
+            var_type: "intensity".to_string(),
+            events: vec![
+                event("2024-01-01T02:00:00Z", "2024-01-01T04:00:00Z", 1),
+                event("2024-01-01T03:00:00Z", "2024-01-01T03:30:00Z", 2),
+                event("2024-01-01T06:00:00Z", "2024-01-01T07:00:00Z", 3),
+            ],
+            default_val: Value::from(0),
+        }
+    }
+
+    #[test]
+    fn event_value_holds_for_its_duration() {
+        let schedule = events();
+        assert_eq!(schedule.floor_search(&time("2024-01-01T01:59:59Z")), 0);
+        assert_eq!(schedule.floor_search(&time("2024-01-01T02:00:00Z")), 1);
+        assert_eq!(schedule.floor_search(&time("2024-01-01T06:30:00Z")), 3);
+        // the end is exclusive
+        assert_eq!(schedule.floor_search(&time("2024-01-01T07:00:00Z")), 0);
+    }
+
+    #[test]
+    fn between_events_reverts_to_default() {
+        let schedule = events();
+        assert_eq!(schedule.floor_search(&time("2024-01-01T04:00:00Z")), 0);
+        assert_eq!(schedule.floor_search(&time("2024-01-01T05:00:00Z")), 0);
+    }
+
+    #[test]
+    fn overlapping_events_later_wins() {
+        let schedule = events();
+        assert_eq!(schedule.floor_search(&time("2024-01-01T03:00:00Z")), 2);
+        assert_eq!(schedule.floor_search(&time("2024-01-01T03:15:00Z")), 2);
+        // the outer event resumes once the inner one ends
+        assert_eq!(schedule.floor_search(&time("2024-01-01T03:30:00Z")), 1);
+    }
+
+    #[test]
+    fn event_schedules_have_no_entry() {
+        let schedule = Schedule::Event(EventSchedule {
+            var_type: "intensity".to_string(),
+            events: Vec::new(),
+            default_val: Value::from(0),
+        });
+        assert!(schedule.to_entry(time("2024-01-01T00:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn segments_and_changes_share_a_limit() {
+        let schedule = Schedule::Periodic(periodic());
+        let start = time("2024-01-01T06:00:00Z");
+        // change points at 12:00, 00:00 and 12:00
+        let end = time("2024-01-02T18:00:00Z");
+        let segments = value_segments(&schedule, &start, &end, 3).unwrap();
+        assert_eq!(segments.len(), 4);
+        assert_eq!(value_changes(&schedule, &start, &end, 3).unwrap().len(), 4);
+        assert!(value_segments(&schedule, &start, &end, 2).is_none());
+        assert!(value_changes(&schedule, &start, &end, 2).is_none());
+    }
+
+    #[test]
+    fn t24_schedules_reject_an_offset_time() {
+        let error = load(
+            "0",
+            r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0, 6],
+                        "Values": [1, 2], "OffsetTime": 2}}"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("led"), "{error}");
+        assert!(
+            error.contains("not allowed to include an offset time"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn follow_changes_a_delay_after_its_source() {
+        let schedules = load(
+            "0",
+            r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0, 6],
+                        "Values": [1, 2]},
+                "echo": {"VariableType": "intensity", "Source": "led", "Delay": "PT10M"},
+                "mapped": {"VariableType": "intensity", "Source": "led", "Delay": "PT10M",
+                           "Map": {"2": 50}}}"#,
+        )
+        .unwrap();
+        let (echo, mapped) = (&schedules["echo"], &schedules["mapped"]);
+        let seen = time("2024-01-02T06:10:00Z");
+        assert_eq!(echo.floor_search(&(seen - TimeDelta::seconds(1))), 1);
+        assert_eq!(echo.floor_search(&seen), 2);
+        // unmapped values give the default
+        assert_eq!(mapped.floor_search(&(seen - TimeDelta::seconds(1))), 0);
+        assert_eq!(mapped.floor_search(&seen), 50);
+        // the source's first point only reaches the follower after the delay
+        assert_eq!(echo.floor_search(&time("2024-01-01T00:05:00Z")), 0);
+        assert_eq!(echo.floor_search(&time("2024-01-01T00:10:00Z")), 1);
+    }
+
+    #[test]
+    fn follow_sources_must_be_periodic() {
+        let error = load(
+            "0",
+            r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0], "Values": [1]},
+                "echo": {"VariableType": "intensity", "Source": "led", "Delay": "PT10M"},
+                "echo2": {"VariableType": "intensity", "Source": "echo", "Delay": "PT10M"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Variable 'echo' followed by 'echo2' must be periodic"
+        );
+    }
+
+    #[test]
+    fn solar_points_follow_the_day_length() {
+        let schedules = load(
+            "-5",
+            r#"{"dawn": {"VariableType": "intensity", "Period": 24, "Times": [0, 1],
+                         "Values": [100, 50], "Solar": "sunrise"}}"#,
+        )
+        .unwrap();
+        let Schedule::Periodic(dawn) = &schedules["dawn"] else {
+            panic!("expected a periodic schedule");
+        };
+        let anchor = dawn.solar.as_ref().unwrap();
+        // New York sunrise: about 07:19 EST in January, 05:25 EDT in June
+        let winter = anchor.event_on(&time("2024-01-15T12:00:00Z")).unwrap();
+        let summer = anchor.event_on(&time("2024-06-15T12:00:00Z")).unwrap();
+        assert!(time("2024-01-15T12:10:00Z") < winter && winter < time("2024-01-15T12:30:00Z"));
+        assert!(time("2024-06-15T09:15:00Z") < summer && summer < time("2024-06-15T09:35:00Z"));
+
+        for sunrise in [winter, summer] {
+            assert_eq!(dawn.floor_search(&(sunrise - TimeDelta::minutes(1))), 0);
+            assert_eq!(dawn.floor_search(&(sunrise + TimeDelta::minutes(30))), 100);
+            assert_eq!(dawn.floor_search(&(sunrise + TimeDelta::minutes(90))), 50);
+        }
+        // the same clock time falls either side of sunrise
+        assert_eq!(dawn.floor_search(&time("2024-01-15T10:00:00Z")), 0);
+        assert_eq!(dawn.floor_search(&time("2024-06-15T10:00:00Z")), 100);
+    }
+
+    #[test]
+    fn carry_in_only_covers_the_region_before_the_first_point() {
+        let mut schedule = PeriodicSchedule::new(
+            "intensity".to_string(),
+            time("2024-01-01T00:00:00Z"),
+            Period::Fixed(TimeDelta::hours(24)),
+            vec![6.0, 18.0],
+            vec![Value::from(1), Value::from(2)],
+            Value::from(0),
+            None,
+        )
+        .unwrap();
+        let first_morning = time("2024-01-01T03:00:00Z");
+        let morning = time("2024-01-02T03:00:00Z");
+        let evening = time("2024-01-02T20:00:00Z");
+        // (carry_in, pre_start) -> values at the three instants
+        let cases = [
+            (false, PreStartBehavior::Default, [0, 0, 2]),
+            (true, PreStartBehavior::Default, [0, 2, 2]),
+            (false, PreStartBehavior::HoldLast, [2, 2, 2]),
+            (true, PreStartBehavior::HoldLast, [2, 2, 2]),
+        ];
+        for (carry_in, pre_start, expected) in cases {
+            schedule.carry_in = carry_in;
+            schedule.pre_start = pre_start;
+            let got = [first_morning, morning, evening].map(|t| schedule.floor_search(&t));
+            assert_eq!(got, expected.map(Value::from), "{carry_in} {pre_start:?}");
+        }
+    }
+
+    #[test]
+    fn transforms_leave_non_numeric_values_alone() {
+        let schedules = load(
+            "0",
+            r#"{"temp": {"VariableType": "intensity", "Value": 100, "Scale": 1.8, "Offset": 32},
+                "hue": {"VariableType": "color", "Value": "red", "Scale": 2}}"#,
+        )
+        .unwrap();
+        let now = time("2024-01-02T00:00:00Z");
+        assert_eq!(schedules["temp"].floor_search(&now), 212.0);
+        assert_eq!(schedules["hue"].floor_search(&now), "red");
+    }
+
+    const STEPS: &str = r#"{"steps": {"VariableType": "intensity", "Breakpoints": [
+        {"Time": "2024-01-01T10:00:00Z", "Value": 5},
+        {"Time": "2024-01-01T14:30:00Z", "Value": 7},
+        {"Time": "2024-01-01T20:00:00Z", "Value": 3}]}}"#;
+
+    #[test]
+    fn breakpoints_hold_until_the_next() {
+        let schedules = load("0", STEPS).unwrap();
+        let steps = &schedules["steps"];
+        assert_eq!(steps.floor_search(&time("2024-01-01T09:59:59Z")), 0);
+        assert_eq!(steps.floor_search(&time("2024-01-01T10:00:00Z")), 5);
+        assert_eq!(steps.floor_search(&time("2024-01-01T14:29:59Z")), 5);
+        assert_eq!(steps.floor_search(&time("2024-01-01T14:30:00Z")), 7);
+        // the last value holds indefinitely, without repeating
+        assert_eq!(steps.floor_search(&time("2024-01-01T20:00:00Z")), 3);
+        assert_eq!(steps.floor_search(&time("2024-01-02T10:00:00Z")), 3);
+    }
+
+    #[test]
+    fn breakpoints_must_be_sorted_and_typed() {
+        let unsorted = STEPS.replace("14:30", "09:30");
+        assert!(matches!(
+            load("0", &unsorted),
+            Err(ScheduleError::BreakpointOrder { index: 1, .. })
+        ));
+        let mistyped = STEPS.replace(r#""Value": 7"#, r#""Value": "red""#);
+        assert!(matches!(
+            load("0", &mistyped),
+            Err(ScheduleError::InvalidBreakpoint { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn next_change_between_points_and_after_the_last() {
+        let schedule = periodic();
+        assert_eq!(
+            schedule.next_change(&time("2024-01-01T06:00:00Z")),
+            Some(time("2024-01-01T12:00:00Z"))
+        );
+        // wraps to the next period's first point
+        assert_eq!(
+            schedule.next_change(&time("2024-01-01T18:00:00Z")),
+            Some(time("2024-01-02T00:00:00Z"))
+        );
+        let constant = ConstantSchedule::new("intensity".to_string(), Value::from(5));
+        assert_eq!(constant.next_change(&time("2024-01-01T06:00:00Z")), None);
+    }
+
+    #[test]
+    fn categorical_values_must_be_listed_categories() {
+        let valid = r#"{"hue": {"VariableType": "color", "Period": 24, "Times": [0, 12],
+                                "Values": ["red", "blue"]},
+                        "tint": {"VariableType": "color", "Value": "off"}}"#;
+        assert!(load("0", valid).is_ok());
+
+        let error = load("0", &valid.replace(r#""blue""#, r#""Bue""#)).unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.contains("'hue'") && message.contains("Bue"),
+            "{message}"
+        );
+        let error = load("0", &valid.replace(r#""off""#, r#""of""#)).unwrap_err();
+        assert!(error.to_string().contains("'tint'"), "{error}");
+    }
+
+    #[test]
+    fn categorical_types_without_categories_take_any_value() {
+        let spec: VariableTypeSpec = serde_json::from_value(serde_json::json!({
+            "VariableType": "Nominal", "DefaultValue": "none", "Description": "free text"
+        }))
+        .unwrap();
+        assert!(check_value_type(&spec, &Value::from("anything")).is_ok());
+    }
+
+    #[test]
+    fn ramp_interpolates_between_anchors_and_clamps_outside() {
+        let schedules = load(
+            "0",
+            r#"{"ramp": {"VariableType": "intensity", "Times": ["2024-01-01T00:00:00Z",
+                         "2024-01-02T00:00:00Z", "2024-01-03T00:00:00Z"], "Values": [0, 50, 10]}}"#,
+        )
+        .unwrap();
+        let ramp = &schedules["ramp"];
+        assert_eq!(ramp.floor_search(&time("2024-01-01T12:00:00Z")), 25.0);
+        assert_eq!(ramp.floor_search(&time("2024-01-02T18:00:00Z")), 20.0);
+        // anchor hits
+        assert_eq!(ramp.floor_search(&time("2024-01-01T00:00:00Z")), 0.0);
+        assert_eq!(ramp.floor_search(&time("2024-01-02T00:00:00Z")), 50.0);
+        // held flat beyond both ends
+        assert_eq!(ramp.floor_search(&time("2023-12-01T00:00:00Z")), 0.0);
+        assert_eq!(ramp.floor_search(&time("2024-02-01T00:00:00Z")), 10.0);
+    }
+
+    #[test]
+    fn ramp_times_must_increase() {
+        let error = load(
+            "0",
+            r#"{"ramp": {"VariableType": "intensity", "Times": ["2024-01-02T00:00:00Z",
+                         "2024-01-01T00:00:00Z"], "Values": [0, 50]}}"#,
+        )
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("strictly increasing (index 1)"),
+            "{error}"
+        );
+    }
+
+    /// `schedules` loaded with intensity bounded to 0..=100
+    fn bounded(schedules: &str) -> Result<ScheduleMap, ScheduleError> {
+        let mut file = file("0", schedules);
+        let spec = file.var_type_specs.get_mut("intensity").unwrap();
+        (spec.min, spec.max) = (Some(0.0), Some(100.0));
+        parse_schedules(file).map(|(schedules, _)| schedules)
+    }
+
+    #[test]
+    fn values_within_bounds_load() {
+        let led = r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0, 12],
+                              "Values": [0, 100]}}"#;
+        assert!(bounded(led).is_ok());
+    }
+
+    #[test]
+    fn values_past_a_bound_are_rejected() {
+        let error =
+            bounded(r#"{"lamp": {"VariableType": "intensity", "Value": 150}}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid value for 'lamp': expected a number from 0 to 100, found 150"
+        );
+        let led = r#"{"led": {"VariableType": "intensity", "Period": 24, "Times": [0, 12],
+                              "Values": [50, -1]}}"#;
+        assert!(bounded(led).unwrap_err().to_string().contains("found -1"));
+        let text = bounded(r#"{"lamp": {"VariableType": "intensity", "Value": "high"}}"#);
+        assert!(text.unwrap_err().to_string().contains("found \"high\""));
+    }
+
+    #[test]
+    fn unbounded_specs_skip_the_range_check() {
+        assert!(load(
+            "0",
+            r#"{"lamp": {"VariableType": "intensity", "Value": 150}}"#
+        )
+        .is_ok());
+    }
+
+    /// start point of a 12h "led" schedule with this OffsetTime
+    fn offset_start(offset: &str) -> Result<DateTime<Utc>, ScheduleError> {
+        let schedules = load(
+            "0",
+            &format!(
+                r#"{{"led": {{"VariableType": "intensity", "Period": 12, "Times": [0, 6],
+                             "Values": [1, 2], "OffsetTime": {offset}}}}}"#
+            ),
+        )?;
+        match &schedules["led"] {
+            Schedule::Periodic(led) => Ok(led.start_point),
+            other => panic!("expected a periodic schedule, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn offset_time_in_hours_or_as_a_duration() {
+        let delayed = time("2024-01-01T01:30:00Z");
+        assert_eq!(offset_start("1.5").unwrap(), delayed);
+        assert_eq!(offset_start(r#""PT90M""#).unwrap(), delayed);
+    }
+
+    #[test]
+    fn offset_time_must_be_a_duration() {
+        let error = offset_start(r#""90 minutes""#).unwrap_err().to_string();
+        assert!(
+            error.starts_with("Failed to parse offset time for 'led'"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn query_times_take_epoch_seconds() {
+        let utc = Zone::default();
+        assert_eq!(
+            parse_query_time("1700000000", &utc).unwrap(),
+            time("2023-11-14T22:13:20Z")
+        );
+        assert_eq!(
+            parse_query_time("1700000000.25", &utc).unwrap(),
+            time("2023-11-14T22:13:20.250Z")
+        );
+        // epochs are absolute, so tz doesn't apply
+        let east = Zone::parse("5").unwrap();
+        assert_eq!(
+            parse_query_time("1700000000", &east).unwrap(),
+            time("2023-11-14T22:13:20Z")
+        );
+        assert!(parse_query_time("1700000000.", &utc).is_err());
+    }
+
+    #[test]
+    fn query_times_still_take_iso_formats() {
+        let utc = Zone::default();
+        let noon = time("2024-01-01T12:00:00Z");
+        for input in [
+            "2024-01-01T12:00:00Z",
+            "2024-01-01T12:00:00",
+            "2024-01-01T120000",
+            // all digits but the T, so not an epoch
+            "20240101T120000",
+        ] {
+            assert_eq!(parse_query_time(input, &utc).unwrap(), noon, "{input}");
+        }
+    }
 }
@@ -1,169 +1,3850 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
+use arc_swap::ArcSwap;
 use axum::{
-    extract::{Query, State},
+    body::Bytes,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, Path, Request, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use axum_extra::extract::Query;
+use chrono::{DateTime, DurationRound, FixedOffset, Offset, SecondsFormat, TimeDelta, Utc};
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use tokio::{sync::watch, time::Interval};
 
 use crate::{
-    lunaluz_deserialization::VariableTypeSpec,
-    schedules::{parse_datetime_iso8601, NamespaceMap, ScheduleMap, VarSchedule},
+    cache::{CacheKey, ResponseCache},
+    clock::Clock,
+    error::ScheduleError,
+    lunaluz_deserialization::{
+        decode_schedule_bytes, parse_schedule_file, FileFormat, LunaLuz, ScheduleEntry,
+        ScheduleInfo, SpecsSection, VarDataType, VariableTypeSpec,
+    },
+    metrics::Metrics,
+    rate_limit::RateLimiter,
+    schedules::{
+        datetime_from_epoch, parse_datetime_iso8601, parse_duration_iso8601, parse_out_tz,
+        parse_query_time, parse_schedules, replace_schedule, td_nanos, value_changes,
+        value_segments, Namespace, NamespaceMap, Schedule, ScheduleMap, ValueTransform,
+        VarSchedule, Zone, ADMIN_VAR_TYPE,
+    },
 };
 
 /// Application state, injected into handlers
 #[derive(Clone)]
 pub struct AppState {
-    pub specs: HashMap<String, VariableTypeSpec>,
-    pub schedules: Arc<ScheduleMap>,
-    pub ext_schedules: Arc<NamespaceMap>
+    /// Path of the loaded schedule file
+    pub filename: PathBuf,
+    /// Format the schedule file is parsed as, on startup and reload
+    pub format: FileFormat,
+    /// Files loaded as extra namespaces, re-read on reload
+    pub namespace_files: Vec<NamespaceFile>,
+    pub specs: Arc<RwLock<HashMap<String, VariableTypeSpec>>>,
+    /// Compiled schedule file; read through `loaded()` for a consistent
+    /// snapshot. Swapped as a whole, so readers never wait on a reload
+    pub loaded: Arc<ArcSwap<LoadedSchedules>>,
+    /// Held while swapping in new schedules, so `/reload` and `PUT /vars`
+    /// don't overwrite each other's changes
+    pub updates: Arc<Mutex<()>>,
+    /// Run `--strict-fields` checks when reloading
+    pub strict_fields: bool,
+    /// How far before the start date the system clock may be before it is flagged
+    pub clock_tolerance: Option<TimeDelta>,
+    /// Serialize floats in plain decimal notation (never `1e-7`)
+    pub plain_numbers: bool,
+    /// Maximum number of timestamps a single request may evaluate
+    pub max_times: usize,
+    /// Bearer token guarding /admin routes
+    pub admin_token: Option<String>,
+    /// Key required by every route but /health (`--api-key`)
+    pub api_key: Option<String>,
+    /// Per-client limit on every route but /health (`--rate-limit`)
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Resolved server configuration, with secrets redacted
+    pub config: Arc<Value>,
+    /// Precomputed `GET /` body served instead of evaluating schedules
+    pub frozen: Option<Arc<FrozenSnapshot>>,
+    /// Recent `GET /` bodies (`--cache-ttl`)
+    pub cache: Option<Arc<ResponseCache>>,
+    /// Grid that "now" query times are snapped down to
+    pub time_grid: Option<TimeDelta>,
+    /// Also snap client-supplied times to the grid
+    pub snap_all_times: bool,
+    pub stats: Arc<ServerStats>,
+    pub metrics: Arc<Metrics>,
+    /// Turns true once the server starts shutting down
+    pub shutdown: watch::Receiver<bool>,
+    /// Namespace used by requests without a `namespace` param; global if None
+    pub default_namespace: Option<String>,
+    /// Source of "now" for requests that don't give a time
+    pub clock: Arc<dyn Clock>,
+}
+
+/// Everything compiled from the schedule file; `/reload` swaps it as a whole
+pub struct LoadedSchedules {
+    pub schedules: ScheduleMap,
+    pub ext_schedules: NamespaceMap,
+    /// Configured schedule timezone, used for `out_tz=local`; a named
+    /// timezone's offset at the start date
+    pub timezone: FixedOffset,
+    /// Parsed experiment start date
+    pub start_date: DateTime<Utc>,
+    pub info: ScheduleInfo,
+}
+
+impl LoadedSchedules {
+    pub fn new(file: LunaLuz) -> Result<Self, ScheduleError> {
+        let start_date = parse_datetime_iso8601(&file.info.start_date)
+            .map_err(|e| ScheduleError::InvalidStartDate(Box::new(e)))?;
+        let timezone = Zone::from_spec(&file.info.timezone)?.offset_at(&start_date);
+        let info = file.info.clone();
+        let (schedules, ext_schedules) = parse_schedules(file)?;
+        Ok(Self {
+            schedules,
+            ext_schedules,
+            timezone,
+            start_date,
+            info,
+        })
+    }
+
+    /// Add a whole schedule file as namespace `name`; its variable type specs
+    /// become the namespace's private specs and its own extensions are ignored
+    pub fn add_namespace(&mut self, name: &str, file: LunaLuz) -> Result<(), String> {
+        if self.ext_schedules.contains_key(name) {
+            return Err(format!("Namespace '{name}' is already defined"));
+        }
+        let specs = file.var_type_specs.clone();
+        let (schedules, _) = parse_schedules(file).map_err(|e| e.to_string())?;
+        let namespace = Namespace { schedules, specs };
+        self.ext_schedules.insert(name.to_string(), namespace);
+        Ok(())
+    }
+}
+
+/// Schedule file loaded as its own namespace (`--namespace name=path`)
+#[derive(Clone, Serialize)]
+pub struct NamespaceFile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Lock-free counters behind `/stats`
+pub struct ServerStats {
+    started: Instant,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    /// incremented on each reload
+    generation: AtomicU64,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            generation: AtomicU64::new(0),
+        }
+    }
+}
+
+impl AppState {
+    /// The currently loaded schedules; held by a request so a concurrent
+    /// reload doesn't change them mid-request
+    pub fn loaded(&self) -> Arc<LoadedSchedules> {
+        self.loaded.load_full()
+    }
+
+    /// Query time used when none is supplied, snapped to the time grid
+    fn now(&self) -> DateTime<Utc> {
+        self.snap(self.clock.now())
+    }
+
+    /// Snap a client-supplied query time, if configured to
+    fn snap_supplied(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if self.snap_all_times {
+            self.snap(time)
+        } else {
+            time
+        }
+    }
+
+    fn snap(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self.time_grid {
+            Some(grid) => time.duration_trunc(grid).unwrap_or(time),
+            None => time,
+        }
+    }
+}
+
+/// Handler error, served with a matching status and a `{"error": ...}` body
+#[derive(Debug)]
+pub enum ApiError {
+    /// malformed or conflicting request parameters (400)
+    BadRequest(String),
+    /// missing or wrong credentials (401)
+    Unauthorized(String),
+    /// unknown namespace or variable (404)
+    NotFound(String),
+    /// client over its `--rate-limit` (429)
+    TooManyRequests(String),
+    /// failures on the server's side, such as an unreadable schedule file (500)
+    Internal(String),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            ApiError::BadRequest(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::TooManyRequests(msg)
+            | ApiError::Internal(msg) => msg,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = json!({ "error": self.message() }).to_string();
+        (status, [(header::CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response()
+    }
+}
+
+/// Schedule errors in a request's parameters are the client's to fix
+impl From<ScheduleError> for ApiError {
+    fn from(error: ScheduleError) -> Self {
+        ApiError::BadRequest(error.to_string())
+    }
+}
+
+fn bad_request(msg: String) -> ApiError {
+    ApiError::BadRequest(msg)
+}
+
+fn not_found(msg: String) -> ApiError {
+    ApiError::NotFound(msg)
+}
+
+/// Resolve the `tz` parameter that naive query times are read in; defaults to UTC
+fn resolve_tz(tz: Option<&str>) -> Result<Zone, ApiError> {
+    Ok(tz.map(Zone::parse).transpose()?.unwrap_or_default())
+}
+
+/// Resolve the `out_tz` parameter; defaults to UTC
+fn resolve_out_tz(loaded: &LoadedSchedules, out_tz: Option<&str>) -> Result<FixedOffset, ApiError> {
+    match out_tz {
+        Some(tz) => Ok(parse_out_tz(tz, loaded.timezone)?),
+        None => Ok(Utc.fix()),
+    }
+}
+
+const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Response encoding negotiated from the `Accept` header or a `format=csv`
+/// query parameter; JSON by default
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    MessagePack,
+    /// only tabular value queries honor it; other responses stay JSON
+    Csv,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let csv_param = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .any(|pair| pair == "format=csv");
+        let accepts = |wanted: &[&str]| {
+            accept.split(',').any(|media| {
+                let media = media.split(';').next().unwrap_or_default().trim();
+                wanted.contains(&media)
+            })
+        };
+        Ok(if csv_param || accepts(&["text/csv"]) {
+            ResponseFormat::Csv
+        } else if accepts(&["application/msgpack", "application/x-msgpack"]) {
+            ResponseFormat::MessagePack
+        } else {
+            ResponseFormat::Json
+        })
+    }
+}
+
+/// JSON response honoring the server's number formatting option;
+/// encoded as MessagePack instead when the client asked for it
+pub struct JsonResponse<T> {
+    body: T,
+    plain_numbers: bool,
+    format: ResponseFormat,
+}
+
+impl<T: Serialize> JsonResponse<T> {
+    fn new(state: &AppState, body: T) -> Self {
+        Self {
+            body,
+            plain_numbers: state.plain_numbers,
+            format: ResponseFormat::Json,
+        }
+    }
+
+    fn with_format(self, format: ResponseFormat) -> Self {
+        Self { format, ..self }
+    }
+}
+
+fn to_json_bytes<T: Serialize>(body: &T, plain_numbers: bool) -> serde_json::Result<Vec<u8>> {
+    if plain_numbers {
+        let mut buf = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, PlainNumberFormatter);
+        body.serialize(&mut ser).map(|_| buf)
+    } else {
+        serde_json::to_vec(body)
+    }
+}
+
+impl<T: Serialize> IntoResponse for JsonResponse<T> {
+    fn into_response(self) -> Response {
+        if self.format == ResponseFormat::MessagePack {
+            // named encoding keeps struct fields as map keys, like the JSON output
+            return match rmp_serde::to_vec_named(&self.body) {
+                Ok(bytes) => {
+                    ([(header::CONTENT_TYPE, "application/msgpack")], bytes).into_response()
+                }
+                Err(e) => ApiError::Internal(e.to_string()).into_response(),
+            };
+        }
+
+        match to_json_bytes(&self.body, self.plain_numbers) {
+            Ok(bytes) => ([(header::CONTENT_TYPE, JSON_CONTENT_TYPE)], bytes).into_response(),
+            Err(e) => ApiError::Internal(e.to_string()).into_response(),
+        }
+    }
+}
+
+/// Writes floats with `Display`, which never uses exponent notation;
+/// whole floats keep a trailing `.0` so they still read as floats
+struct PlainNumberFormatter;
+
+impl serde_json::ser::Formatter for PlainNumberFormatter {
+    fn write_f64<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        let text = value.to_string();
+        writer.write_all(text.as_bytes())?;
+        if !text.contains('.') {
+            writer.write_all(b".0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Namespace ID a request refers to, falling back to `--default-namespace`
+fn effective_namespace<'a>(state: &'a AppState, namespace: Option<&'a str>) -> Option<&'a str> {
+    namespace.or(state.default_namespace.as_deref())
+}
+
+/// Resolve the `namespace` parameter; defaults to the default namespace,
+/// which is the global namespace unless configured otherwise
+fn resolve_namespace<'a>(
+    state: &AppState,
+    loaded: &'a LoadedSchedules,
+    namespace: Option<&str>,
+) -> Result<&'a ScheduleMap, ApiError> {
+    match effective_namespace(state, namespace) {
+        Some(id) => loaded
+            .ext_schedules
+            .get(id)
+            .map(|namespace| &namespace.schedules)
+            .ok_or_else(|| not_found(format!("Unknown Namespace: '{id}'"))),
+        None => Ok(&loaded.schedules),
+    }
+}
+
+/// Look up a variable type's spec, preferring the namespace's private specs
+fn find_spec(
+    state: &AppState,
+    loaded: &LoadedSchedules,
+    namespace: Option<&str>,
+    var_type: &str,
+) -> Option<VariableTypeSpec> {
+    let private = effective_namespace(state, namespace)
+        .and_then(|id| loaded.ext_schedules.get(id))
+        .and_then(|namespace| namespace.specs.get(var_type));
+    match private {
+        Some(spec) => Some(spec.clone()),
+        None => state.specs.read().unwrap().get(var_type).cloned(),
+    }
+}
+
+/// Schedules shown in value output; Administrative variables (including
+/// extension extras) are bookkeeping and only shown when requested
+fn visible_schedules<'a>(
+    state: &AppState,
+    loaded: &LoadedSchedules,
+    namespace: Option<&str>,
+    schedules: &'a ScheduleMap,
+    include_admin: bool,
+) -> Vec<(&'a String, &'a Schedule)> {
+    if include_admin {
+        return schedules.iter().collect();
+    }
+
+    schedules
+        .iter()
+        .filter(|(name, schedule)| {
+            let var_type = schedule.var_type();
+            if var_type == ADMIN_VAR_TYPE {
+                return false;
+            }
+            // a type without a spec is unknown rather than administrative
+            match find_spec(state, loaded, namespace, &var_type) {
+                Some(spec) => !matches!(spec.var_type, VarDataType::Administrative),
+                None => {
+                    tracing::warn!("no spec for variable type '{var_type}' of '{name}'");
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+// zero offsets keep serializing as "Z", matching the plain UTC output
+fn serialize_time<S: Serializer>(time: &DateTime<FixedOffset>, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+fn serialize_times<S: Serializer>(
+    times: &[DateTime<FixedOffset>],
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.collect_seq(
+        times
+            .iter()
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    )
 }
 
 /// Query parameters for root endpoint
 #[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
 pub struct GetVarsParams {
-    /// UTC ISO‑8601 timestamp, defaults to now
-    pub time: Option<String>,
+    /// UTC ISO‑8601 timestamp(s) or epoch seconds, defaults to now; repeat `time=` for several
+    #[serde(default)]
+    pub time: Vec<String>,
     /// Include variable types in response; defaults to false
     #[serde(rename = "var_type", alias = "include_types", default)]
     pub include_types: bool,
     /// Namespace ID (used by extensions with private namespaces)
     pub namespace: Option<String>,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    pub tz: Option<String>,
+    /// Timezone of response timestamps: "utc" (default), "local", or an offset like "-05:00"
+    pub out_tz: Option<String>,
+    /// How variable types are included; `inline` implies `include_types` and
+    /// needs a single time
+    #[serde(default)]
+    pub types: TypesMode,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    pub include_admin: bool,
+    /// Serve numeric values as `value * scale + offset`, replacing each
+    /// variable's own Scale/Offset
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    /// Which schedule point a value comes from; defaults to floor
+    #[serde(default)]
+    pub mode: SearchMode,
+}
+
+/// Which schedule point a queried value comes from
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// the most recent point at or before the time
+    #[default]
+    Floor,
+    /// the next point at or after the time
+    Ceil,
+    /// whichever point is closest to the time
+    Nearest,
+}
+
+impl SearchMode {
+    fn search(self, schedule: &Schedule, time: &DateTime<Utc>) -> Value {
+        match self {
+            SearchMode::Floor => schedule.floor_search(time),
+            SearchMode::Ceil => schedule.ceil_search(time),
+            SearchMode::Nearest => schedule.nearest_search(time),
+        }
+    }
+}
+
+/// Layout of variable types in single-time responses
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TypesMode {
+    /// separate `var_types` map parallel to `values`
+    #[default]
+    Map,
+    /// each value becomes `{ "value": ..., "type": ... }`
+    Inline,
 }
 
 /// Response structure for root endpoint
 #[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GetScheduleResponse {
-    time: DateTime<Utc>,
+    #[serde(serialize_with = "serialize_time")]
+    time: DateTime<FixedOffset>,
     values: HashMap<String, Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     var_types: Option<HashMap<String, String>>,
+    /// units of the variables whose type spec has one, alongside `var_types`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    units: Option<HashMap<String, String>>,
 }
 
 /// Handler to get all variable values at a given time
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/",
+    params(GetVarsParams),
+    responses(
+        (status = 200, body = GetScheduleResponse),
+        (status = 400, description = "Malformed or conflicting parameters"),
+        (status = 404, description = "Unknown namespace"),
+    ),
+))]
 pub async fn get_vars(
     State(state): State<AppState>,
+    format: ResponseFormat,
     Query(payload): Query<GetVarsParams>,
-) -> Result<Json<GetScheduleResponse>, String> {
+) -> Result<Response, ApiError> {
+    if let Some(frozen) = &state.frozen {
+        return frozen_response(&state, frozen, &payload);
+    }
+
+    let loaded = state.loaded();
+    let out_tz = resolve_out_tz(&loaded, payload.out_tz.as_deref())?;
+
+    if payload.time.len() > state.max_times {
+        return Err(bad_request(format!(
+            "Requested {} times; at most {} are allowed",
+            payload.time.len(),
+            state.max_times
+        )));
+    }
+
+    let tz = resolve_tz(payload.tz.as_deref())?;
+    let times = payload
+        .time
+        .iter()
+        .map(|t| parse_query_time(t, &tz).map(|t| state.snap_supplied(t)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let schedules = resolve_namespace(&state, &loaded, payload.namespace.as_deref())?;
+    let namespace = payload.namespace.as_deref();
+    let schedules = visible_schedules(&state, &loaded, namespace, schedules, payload.include_admin);
+    let transform = request_transform(payload.scale, payload.offset);
+    let labels = QueryLabels::new(&state, namespace, schedules.len());
+
+    // repeated time params get the multi-time response used by post_vars
+    if times.len() > 1 {
+        if payload.types == TypesMode::Inline {
+            return Err(bad_request(
+                "types=inline is only supported for a single time".to_string(),
+            ));
+        }
+        let units = payload
+            .include_types
+            .then(|| variable_units(&state, &loaded, namespace, &schedules));
+        let var_types = payload.include_types.then(|| {
+            schedules
+                .iter()
+                .map(|(var, schedule)| (var.to_string(), schedule.var_type()))
+                .collect()
+        });
+        let values = schedules
+            .into_iter()
+            .map(|(var, schedule)| {
+                (
+                    var.clone(),
+                    served_values(schedule, &times, transform, payload.mode),
+                )
+            })
+            .collect();
+        // headers describe the first requested time
+        let first = times[0];
+        let times: Vec<_> = times.iter().map(|t| t.with_timezone(&out_tz)).collect();
+        let mut response = if format == ResponseFormat::Csv {
+            values_csv(&state, &times, values)
+        } else {
+            let response = PostScheduleResponse {
+                times,
+                values,
+                var_types,
+                units,
+            };
+            JsonResponse::new(&state, response)
+                .with_format(format)
+                .into_response()
+        };
+        set_schedule_headers(&loaded, &mut response, first);
+        return Ok(labels.attach(response));
+    }
+
     // Determine query time
-    let time = match payload.time {
-        Some(t) => parse_datetime_iso8601(&t)?,
-        None => Utc::now(),
+    let time = times.first().copied().unwrap_or_else(|| state.now());
+    let units = (payload.include_types && payload.types == TypesMode::Map)
+        .then(|| variable_units(&state, &loaded, namespace, &schedules));
+
+    let cache = state.cache.as_ref().filter(|_| cacheable(&payload, format));
+    if let Some(cache) = cache {
+        let time = cache.round(time);
+        let key = CacheKey {
+            namespace: payload.namespace.clone(),
+            time,
+            include_types: payload.include_types,
+            include_admin: payload.include_admin,
+        };
+        let body = match cache.get(&key) {
+            Some(body) => body,
+            None => {
+                let mut response = schedule_response(&schedules, time, out_tz, &payload);
+                response.units = units;
+                let body = to_json_bytes(&response, state.plain_numbers)
+                    .map_err(|e| ApiError::Internal(e.to_string()))?;
+                let body = Bytes::from(body);
+                cache.insert(key, body.clone());
+                body
+            }
+        };
+        let mut response = ([(header::CONTENT_TYPE, JSON_CONTENT_TYPE)], body).into_response();
+        set_schedule_headers(&loaded, &mut response, time);
+        return Ok(labels.attach(response));
+    }
+
+    let mut response = schedule_response(&schedules, time, out_tz, &payload);
+    response.units = units;
+    let mut response = if format == ResponseFormat::Csv {
+        let values = response
+            .values
+            .into_iter()
+            .map(|(var, value)| (var, vec![value]))
+            .collect();
+        values_csv(&state, &[response.time], values)
+    } else {
+        JsonResponse::new(&state, response)
+            .with_format(format)
+            .into_response()
     };
+    set_schedule_headers(&loaded, &mut response, time);
+    Ok(labels.attach(response))
+}
+
+/// Namespace and variable count of a value query, passed to `count_requests`
+/// in a response extension for the per-namespace metrics
+#[derive(Clone)]
+pub struct QueryLabels {
+    /// resolved namespace, or "global"
+    namespace: String,
+    variables: usize,
+}
+
+impl QueryLabels {
+    fn new(state: &AppState, namespace: Option<&str>, variables: usize) -> Self {
+        let namespace = effective_namespace(state, namespace).unwrap_or("global");
+        Self {
+            namespace: namespace.to_string(),
+            variables,
+        }
+    }
+
+    fn attach(self, mut response: Response) -> Response {
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Whether a single-time query differs only in what the response cache is
+/// keyed by
+fn cacheable(payload: &GetVarsParams, format: ResponseFormat) -> bool {
+    format == ResponseFormat::Json
+        && payload.out_tz.is_none()
+        && payload.types == TypesMode::Map
+        && payload.scale.is_none()
+        && payload.offset.is_none()
+        && payload.mode == SearchMode::Floor
+}
 
-    // Collect current values and (optionally) variable types
+/// Collect the values (and optionally types) of `schedules` at `time`
+fn schedule_response(
+    schedules: &[(&String, &Schedule)],
+    time: DateTime<Utc>,
+    out_tz: FixedOffset,
+    payload: &GetVarsParams,
+) -> GetScheduleResponse {
     let mut values = HashMap::new();
     let mut types = HashMap::new();
 
-    let schedules = match payload.namespace {
-        Some(id) => state.ext_schedules.get(&id).ok_or(format!("Unknown Namespace: '{id}'"))?,
-        None => &state.schedules,
-    };
+    let inline_types = payload.types == TypesMode::Inline;
+    let transform = request_transform(payload.scale, payload.offset);
+
+    for (var, schedule) in schedules {
+        let value = served_value(schedule, &time, transform, payload.mode);
 
-    for (var, schedule) in schedules.iter() {
-        let value = schedule.floor_search(&time);
-        values.insert(var.clone(), value);
+        if inline_types {
+            let entry = json!({ "value": value, "type": schedule.var_type() });
+            values.insert(var.to_string(), entry);
+            continue;
+        }
+        values.insert(var.to_string(), value);
 
         if payload.include_types {
-            types.insert(var.clone(), schedule.var_type());
+            types.insert(var.to_string(), schedule.var_type());
         }
     }
 
-    let var_types = if payload.include_types {
+    let var_types = if payload.include_types && !inline_types {
         Some(types)
     } else {
         None
     };
 
-    Ok(Json(GetScheduleResponse {
-        time,
+    GetScheduleResponse {
+        time: time.with_timezone(&out_tz),
         values,
         var_types,
-    }))
+        units: None,
+    }
+}
+
+/// Units of `schedules` from their type specs; variables without one are left out
+fn variable_units(
+    state: &AppState,
+    loaded: &LoadedSchedules,
+    namespace: Option<&str>,
+    schedules: &[(&String, &Schedule)],
+) -> HashMap<String, String> {
+    schedules
+        .iter()
+        .filter_map(|(var, schedule)| {
+            let spec = find_spec(state, loaded, namespace, &schedule.var_type())?;
+            Some((var.to_string(), spec.unit?))
+        })
+        .collect()
+}
+
+// let lightweight monitors branch on headers without parsing the body
+fn set_schedule_headers(loaded: &LoadedSchedules, response: &mut Response, time: DateTime<Utc>) {
+    let headers = response.headers_mut();
+    let started = if time >= loaded.start_date {
+        "true"
+    } else {
+        "false"
+    };
+    headers.insert("x-schedule-started", HeaderValue::from_static(started));
+    if let Ok(name) = HeaderValue::from_str(&loaded.info.experiment_name) {
+        headers.insert("x-experiment-name", name);
+    }
+}
+
+/// `GET /` body evaluated once at a fixed time (`--freeze-at`); served as-is,
+/// so values never change while the server runs
+pub struct FrozenSnapshot {
+    time: DateTime<Utc>,
+    body: Bytes,
+}
+
+impl FrozenSnapshot {
+    /// Evaluate the global namespace with default parameters at `time`
+    pub fn new(state: &AppState, time: DateTime<Utc>) -> Result<Self, String> {
+        let params = GetVarsParams {
+            time: Vec::new(),
+            include_types: false,
+            namespace: None,
+            tz: None,
+            out_tz: None,
+            types: TypesMode::Map,
+            include_admin: false,
+            scale: None,
+            offset: None,
+            mode: SearchMode::Floor,
+        };
+        let loaded = state.loaded();
+        let schedules = visible_schedules(state, &loaded, None, &loaded.schedules, false);
+        let response = schedule_response(&schedules, time, Utc.fix(), &params);
+        let body = to_json_bytes(&response, state.plain_numbers).map_err(|e| e.to_string())?;
+        Ok(Self {
+            time,
+            body: Bytes::from(body),
+        })
+    }
+}
+
+fn frozen_response(
+    state: &AppState,
+    frozen: &FrozenSnapshot,
+    payload: &GetVarsParams,
+) -> Result<Response, ApiError> {
+    if !payload.time.is_empty() {
+        return Err(bad_request(format!(
+            "Server is frozen at {}; the time parameter is not supported",
+            frozen.time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+        )));
+    }
+    if payload.mode != SearchMode::Floor {
+        return Err(bad_request(
+            "Server is frozen; only the floor mode is supported".to_string(),
+        ));
+    }
+
+    let mut response = (
+        [(header::CONTENT_TYPE, JSON_CONTENT_TYPE)],
+        frozen.body.clone(),
+    )
+        .into_response();
+    set_schedule_headers(&state.loaded(), &mut response, frozen.time);
+    Ok(response)
+}
+
+/// Scale/Offset given with a request; they replace the variables' own
+fn request_transform(scale: Option<f64>, offset: Option<f64>) -> Option<ValueTransform> {
+    if scale.is_none() && offset.is_none() {
+        return None;
+    }
+    Some(ValueTransform {
+        scale: scale.unwrap_or(1.0),
+        offset: offset.unwrap_or(0.0),
+    })
+}
+
+/// Value served at `time`, under the request's transform if it has one
+fn served_value(
+    schedule: &Schedule,
+    time: &DateTime<Utc>,
+    transform: Option<ValueTransform>,
+    mode: SearchMode,
+) -> Value {
+    match transform {
+        Some(transform) => transform.apply(mode.search(schedule.raw(), time)),
+        None => mode.search(schedule, time),
+    }
 }
 
-/// Handler to return variable type specs
-pub async fn get_specs(State(state): State<AppState>) -> Json<HashMap<String, VariableTypeSpec>> {
-    Json(state.specs.clone())
+fn served_values(
+    schedule: &Schedule,
+    times: &[DateTime<Utc>],
+    transform: Option<ValueTransform>,
+    mode: SearchMode,
+) -> Vec<Value> {
+    let schedule = match transform {
+        Some(_) => schedule.raw(),
+        None => schedule,
+    };
+    let values = match mode {
+        SearchMode::Floor => schedule.floor_multi_search(times),
+        _ => times.iter().map(|t| mode.search(schedule, t)).collect(),
+    };
+    match transform {
+        Some(transform) => values.into_iter().map(|v| transform.apply(v)).collect(),
+        None => values,
+    }
 }
 
+/// Query parameters for the live update stream
 #[derive(Deserialize)]
-pub struct ScheduleQuery {
-    /// UTC ISO‑8601 timestamp, defaults to now
+pub struct StreamParams {
+    /// Namespace ID (used by extensions with private namespaces)
+    pub namespace: Option<String>,
+    /// Seconds between evaluations, at least 0.1; defaults to 1
+    pub interval: Option<f64>,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    pub include_admin: bool,
+}
+
+/// Per-connection state of a live update stream
+struct StreamState {
+    state: AppState,
+    params: StreamParams,
+    ticks: Interval,
+    /// values sent with the previous event
+    last: Option<HashMap<String, Value>>,
+}
+
+/// Shortest `/stream` interval; each tick evaluates every variable
+const MIN_STREAM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Handler streaming all variable values as Server-Sent Events; a tick only
+/// sends an event when some value changed since the previous one
+pub async fn get_stream(
+    State(state): State<AppState>,
+    Query(params): Query<StreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let seconds = params.interval.unwrap_or(1.0);
+    let period = Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|period| *period >= MIN_STREAM_INTERVAL)
+        .ok_or_else(|| {
+            bad_request(format!(
+                "Interval must be at least 0.1 seconds, got {seconds}"
+            ))
+        })?;
+    // fail with a status code rather than an empty stream
+    resolve_namespace(&state, &state.loaded(), params.namespace.as_deref())?;
+
+    let seed = StreamState {
+        state,
+        params,
+        ticks: tokio::time::interval(period),
+        last: None,
+    };
+    // the stream is dropped with its connection, which stops the ticks
+    let events = stream::unfold(seed, |mut stream| async move {
+        loop {
+            tokio::select! {
+                _ = stream.ticks.tick() => {}
+                // end the stream, or a graceful shutdown would wait on it forever
+                _ = stream.state.shutdown.wait_for(|down| *down) => return None,
+            }
+            let time = stream.state.now();
+            let loaded = stream.state.loaded();
+            let namespace = stream.params.namespace.as_deref();
+            // a reload can remove the namespace; end the stream then
+            let schedules = resolve_namespace(&stream.state, &loaded, namespace).ok()?;
+            let include_admin = stream.params.include_admin;
+            let values: HashMap<String, Value> =
+                visible_schedules(&stream.state, &loaded, namespace, schedules, include_admin)
+                    .into_iter()
+                    .map(|(var, schedule)| (var.clone(), schedule.floor_search(&time)))
+                    .collect();
+            if stream.last.as_ref() == Some(&values) {
+                continue;
+            }
+
+            let response = GetScheduleResponse {
+                time: time.fixed_offset(),
+                values,
+                var_types: None,
+                units: None,
+            };
+            let data = to_json_bytes(&response, stream.state.plain_numbers).ok()?;
+            let event = Event::default().data(String::from_utf8(data).ok()?);
+            stream.last = Some(response.values);
+            return Some((Ok(event), stream));
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HealthResponse {
+    status: &'static str,
+    variables: usize,
+    namespaces: usize,
+}
+
+/// Handler for liveness/readiness probes; only counts what is loaded
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, body = HealthResponse)),
+))]
+pub async fn get_health(State(state): State<AppState>) -> JsonResponse<HealthResponse> {
+    let loaded = state.loaded();
+    let response = HealthResponse {
+        status: "ok",
+        variables: loaded.schedules.len(),
+        namespaces: loaded.ext_schedules.len(),
+    };
+    JsonResponse::new(&state, response)
+}
+
+/// Handler returning the experiment metadata from the schedule file's Info section
+pub async fn get_info(State(state): State<AppState>) -> JsonResponse<ScheduleInfo> {
+    JsonResponse::new(&state, state.loaded().info.clone())
+}
+
+/// Handler exporting a namespace's compiled schedules, with defaults applied
+/// and start points resolved, keyed (and sorted) by variable name
+pub async fn get_export(
+    State(state): State<AppState>,
+    Query(params): Query<NamespaceParams>,
+) -> Result<JsonResponse<BTreeMap<String, Schedule>>, ApiError> {
+    let loaded = state.loaded();
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let export = schedules
+        .iter()
+        .map(|(var, schedule)| (var.clone(), schedule.clone()))
+        .collect();
+    Ok(JsonResponse::new(&state, export))
+}
+
+/// Handler to return variable type specs; a namespace's specs include the
+/// global specs plus the types private to it
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/specs",
+    params(NamespaceParams),
+    responses(
+        (status = 200, body = HashMap<String, VariableTypeSpec>),
+        (status = 404, description = "Unknown namespace"),
+    ),
+))]
+pub async fn get_specs(
+    State(state): State<AppState>,
+    Query(params): Query<NamespaceParams>,
+) -> Result<JsonResponse<HashMap<String, VariableTypeSpec>>, ApiError> {
+    let mut specs = state.specs.read().unwrap().clone();
+    if let Some(id) = effective_namespace(&state, params.namespace.as_deref()) {
+        let loaded = state.loaded();
+        let namespace = loaded
+            .ext_schedules
+            .get(id)
+            .ok_or_else(|| not_found(format!("Unknown Namespace: '{id}'")))?;
+        specs.extend(namespace.specs.clone());
+    }
+    Ok(JsonResponse::new(&state, specs))
+}
+
+/// Handler for the spec of a single variable type
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/specs/{var_type}",
+    params(("var_type" = String, Path, description = "Variable type name"), NamespaceParams),
+    responses(
+        (status = 200, body = VariableTypeSpec),
+        (status = 404, description = "Unknown namespace or variable type"),
+    ),
+))]
+pub async fn get_spec(
+    State(state): State<AppState>,
+    Path(var_type): Path<String>,
+    Query(params): Query<NamespaceParams>,
+) -> Result<JsonResponse<VariableTypeSpec>, ApiError> {
+    let loaded = state.loaded();
+    let namespace = params.namespace.as_deref();
+    resolve_namespace(&state, &loaded, namespace)?;
+    let spec = find_spec(&state, &loaded, namespace, &var_type)
+        .ok_or_else(|| not_found(format!("Unknown variable type: '{var_type}'")))?;
+    Ok(JsonResponse::new(&state, spec))
+}
+
+#[derive(Deserialize)]
+pub struct SnapshotParams {
+    /// UTC ISO‑8601 timestamp or epoch seconds, defaults to now
     time: Option<String>,
-    /// UTC ISO‑8601 timestamps, defaults to now
-    times: Option<Vec<String>>,
-    /// Names of requested variables
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    include_admin: bool,
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Handler returning all variables' values at a time as `variable,value` CSV rows
+pub async fn get_snapshot_csv(
+    State(state): State<AppState>,
+    Query(params): Query<SnapshotParams>,
+) -> Result<Response, ApiError> {
+    let tz = resolve_tz(params.tz.as_deref())?;
+    let time = match params.time {
+        Some(t) => state.snap_supplied(parse_query_time(&t, &tz)?),
+        None => state.now(),
+    };
+
+    let loaded = state.loaded();
+
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let namespace = params.namespace.as_deref();
+    let mut schedules =
+        visible_schedules(&state, &loaded, namespace, schedules, params.include_admin);
+    schedules.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut csv = String::from("variable,value\n");
+    for (var, schedule) in schedules {
+        let value = csv_value(schedule.floor_search(&time), state.plain_numbers);
+        csv.push_str(&format!("{},{}\n", csv_field(var), csv_field(&value)));
+    }
+
+    Ok(([(header::CONTENT_TYPE, CSV_CONTENT_TYPE)], csv).into_response())
+}
+
+/// Text of a value in a CSV cell; strings go in as-is, everything else is
+/// JSON-encoded
+fn csv_value(value: Value, plain_numbers: bool) -> String {
+    match value {
+        Value::String(s) => s,
+        other => to_json_bytes(&other, plain_numbers)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_default(),
+    }
+}
+
+/// CSV table with a `time` column and one column per variable (sorted by
+/// name), one row per time
+fn values_csv(
+    state: &AppState,
+    times: &[DateTime<FixedOffset>],
+    values: HashMap<String, Vec<Value>>,
+) -> Response {
+    let mut columns: Vec<(String, Vec<Value>)> = values.into_iter().collect();
+    columns.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let header =
+        std::iter::once("time".to_string()).chain(columns.iter().map(|(var, _)| csv_field(var)));
+    let mut csv = header.collect::<Vec<_>>().join(",");
+    csv.push('\n');
+    for (row, time) in times.iter().enumerate() {
+        let time = time.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        let cells = columns.iter().map(|(_, values)| {
+            let value = values.get(row).cloned().unwrap_or(Value::Null);
+            csv_field(&csv_value(value, state.plain_numbers))
+        });
+        let cells: Vec<String> = std::iter::once(time).chain(cells).collect();
+        csv.push_str(&cells.join(","));
+        csv.push('\n');
+    }
+
+    ([(header::CONTENT_TYPE, CSV_CONTENT_TYPE)], csv).into_response()
+}
+
+/// Query time as a UTC ISO‑8601 string or a number of Unix epoch seconds
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum TimeInput {
+    Iso(String),
+    Epoch(f64),
+}
+
+impl TimeInput {
+    fn parse(&self, tz: &Zone) -> Result<DateTime<Utc>, ScheduleError> {
+        match self {
+            TimeInput::Iso(t) => parse_query_time(t, tz),
+            TimeInput::Epoch(seconds) => datetime_from_epoch(*seconds),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScheduleQuery {
+    /// UTC ISO‑8601 timestamp or epoch seconds, defaults to now
+    time: Option<TimeInput>,
+    /// UTC ISO‑8601 timestamps and/or epoch seconds, defaults to now
+    times: Option<Vec<TimeInput>>,
+    /// Sample every `step` (ISO 8601 duration) from `from` to `to`, both
+    /// UTC ISO‑8601 and inclusive, instead of listing `times`
+    from: Option<String>,
+    to: Option<String>,
+    step: Option<String>,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// Names of requested variables; a trailing `*` selects every variable
+    /// with that prefix
     vars: Option<Vec<String>>,
     /// Namespace ID (defaults to global namespace)
     namespace: Option<String>,
+    /// Timezone of response timestamps: "utc" (default), "local", or an offset like "-05:00"
+    out_tz: Option<String>,
+    /// Include Administrative variables when `vars` is omitted; they are
+    /// hidden by default, but explicitly named variables are always returned
+    #[serde(default)]
+    include_admin: bool,
+    /// Serve numeric values as `value * scale + offset`, replacing each
+    /// variable's own Scale/Offset
+    scale: Option<f64>,
+    offset: Option<f64>,
+    /// Which schedule point a value comes from; defaults to floor
+    #[serde(default)]
+    mode: SearchMode,
+    /// Include each variable's type in a `var_types` map
+    #[serde(default)]
+    include_types: bool,
 }
 
 // ? Should I add support for single-val returns
 // - allow return of "time" and skip serialization if None
 // - would need clearly communicated
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct PostScheduleResponse {
-    times: Vec<DateTime<Utc>>,
+    #[serde(serialize_with = "serialize_times")]
+    times: Vec<DateTime<FixedOffset>>,
     values: HashMap<String, Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    var_types: Option<HashMap<String, String>>,
+    /// units of the variables whose type spec has one, alongside `var_types`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    units: Option<HashMap<String, String>>,
 }
 
+/// Handler for values of selected variables at one or more times
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/vars",
+    request_body = ScheduleQuery,
+    responses(
+        (status = 200, body = PostScheduleResponse),
+        (status = 400, description = "Malformed or conflicting query"),
+        (status = 404, description = "Unknown namespace or variable"),
+    ),
+))]
 pub async fn post_vars(
     State(state): State<AppState>,
+    format: ResponseFormat,
     Json(payload): Json<ScheduleQuery>,
-) -> Result<Json<PostScheduleResponse>, String> {
+) -> Result<Response, ApiError> {
+    let namespace = payload.namespace.clone();
+    let replies = evaluate_query(&state, payload)?;
+    let labels = QueryLabels::new(&state, namespace.as_deref(), replies.values.len());
+    if format == ResponseFormat::Csv {
+        return Ok(labels.attach(values_csv(&state, &replies.times, replies.values)));
+    }
+    let response = JsonResponse::new(&state, replies).with_format(format);
+    Ok(labels.attach(response.into_response()))
+}
+
+/// Evaluate one `POST /vars` query
+fn evaluate_query(
+    state: &AppState,
+    payload: ScheduleQuery,
+) -> Result<PostScheduleResponse, ApiError> {
     if payload.time.is_some() && payload.times.is_some() {
-        return Err("Bad request; included both time and times".to_string());
+        return Err(bad_request(
+            "Bad request; included both time and times".to_string(),
+        ));
+    }
+    let sampling = payload.from.is_some() || payload.to.is_some() || payload.step.is_some();
+    if sampling && (payload.time.is_some() || payload.times.is_some()) {
+        return Err(bad_request(
+            "Bad request; from, to and step can't be combined with time or times".to_string(),
+        ));
     }
 
-    let schedules = match payload.namespace {
-        Some(id) => state.ext_schedules.get(&id).ok_or(format!("Unknown Namespace: '{id}'"))?,
-        None => &state.schedules,
-    };
+    let tz = resolve_tz(payload.tz.as_deref())?;
+    let loaded = state.loaded();
+    let out_tz = resolve_out_tz(&loaded, payload.out_tz.as_deref())?;
 
+    let schedules = resolve_namespace(state, &loaded, payload.namespace.as_deref())?;
+
+    let visible = || {
+        visible_schedules(
+            state,
+            &loaded,
+            payload.namespace.as_deref(),
+            schedules,
+            payload.include_admin,
+        )
+    };
     let vars: Vec<String> = match payload.vars {
         Some(var_list) => {
-            if !var_list.iter().all(|v| schedules.contains_key(v)) {
-                return Err("Requested one or more unknown variables".to_string());
-            };
-            var_list
+            let mut vars = Vec::new();
+            for entry in var_list {
+                // `prefix*` selects every visible variable starting with prefix
+                let Some(prefix) = entry.strip_suffix('*') else {
+                    if !schedules.contains_key(&entry) {
+                        return Err(not_found(
+                            "Requested one or more unknown variables".to_string(),
+                        ));
+                    }
+                    vars.push(entry);
+                    continue;
+                };
+                let mut matched: Vec<String> = visible()
+                    .into_iter()
+                    .map(|(v, _)| v.to_string())
+                    .filter(|v| v.starts_with(prefix))
+                    .collect();
+                if matched.is_empty() {
+                    return Err(not_found(format!("Pattern '{entry}' matches no variables")));
+                }
+                matched.sort();
+                vars.extend(matched);
+            }
+            let mut seen = HashSet::new();
+            vars.retain(|v| seen.insert(v.clone()));
+            vars
         }
-        None => schedules.keys().map(|v| v.to_string()).collect(),
+        None => visible().into_iter().map(|(v, _)| v.to_string()).collect(),
     };
 
-    let replies = if let Some(times) = payload.times {
-        let times: Result<Vec<DateTime<Utc>>, String> = times
-            .iter()
-            .map(|t| parse_datetime_iso8601(&t))
-            .collect();
-        let times = times?;
+    let transform = request_transform(payload.scale, payload.offset);
+    let var_types = payload.include_types.then(|| {
+        vars.iter()
+            .map(|var| (var.clone(), schedules[var].var_type()))
+            .collect()
+    });
+
+    let times = match payload.times {
+        Some(times) => {
+            if times.len() > state.max_times {
+                return Err(bad_request(format!(
+                    "Requested {} times; at most {} are allowed",
+                    times.len(),
+                    state.max_times
+                )));
+            }
+            let times: Result<Vec<DateTime<Utc>>, ScheduleError> = times
+                .iter()
+                .map(|t| t.parse(&tz).map(|t| state.snap_supplied(t)))
+                .collect();
+            Some(times?)
+        }
+        None if sampling => Some(sample_times(
+            state,
+            payload.from.as_deref(),
+            payload.to.as_deref(),
+            payload.step.as_deref(),
+            &tz,
+        )?),
+        None => None,
+    };
 
+    let replies = if let Some(times) = times {
         let mut values = HashMap::new();
         for var in vars.into_iter() {
             let schedule = &schedules[&var];
-            let var_values = schedule.floor_multi_search(&times);
+            let var_values = served_values(schedule, &times, transform, payload.mode);
             values.insert(var, var_values);
         }
-        PostScheduleResponse { times, values }
+        let times = times.iter().map(|t| t.with_timezone(&out_tz)).collect();
+        PostScheduleResponse {
+            times,
+            values,
+            var_types,
+            units: None,
+        }
     } else {
         let time = match payload.time {
-            Some(t) => parse_datetime_iso8601(&t)?,
-            None => Utc::now(),
+            Some(t) => state.snap_supplied(t.parse(&tz)?),
+            None => state.now(),
         };
 
-        let times = vec![time];
+        let times = vec![time.with_timezone(&out_tz)];
         let mut values = HashMap::new();
 
         for var in vars.iter() {
             let schedule = &schedules[var];
-            let value = schedule.floor_search(&time);
+            let value = served_value(schedule, &time, transform, payload.mode);
             values.insert(var.clone(), vec![value]);
         }
 
-        PostScheduleResponse { times, values }
+        PostScheduleResponse {
+            times,
+            values,
+            var_types,
+            units: None,
+        }
+    };
+
+    Ok(replies)
+}
+
+/// Every `step` from `from` through `to`, for a sampling `POST /vars` query
+fn sample_times(
+    state: &AppState,
+    from: Option<&str>,
+    to: Option<&str>,
+    step: Option<&str>,
+    tz: &Zone,
+) -> Result<Vec<DateTime<Utc>>, ApiError> {
+    let (Some(from), Some(to), Some(step)) = (from, to, step) else {
+        return Err(bad_request(
+            "Sampling needs all of from, to and step".to_string(),
+        ));
+    };
+    let start = parse_query_time(from, tz)?;
+    let end = parse_query_time(to, tz)?;
+    let delta = parse_duration_iso8601(step)?;
+    if delta <= TimeDelta::zero() {
+        return Err(bad_request("step must be a positive duration".to_string()));
+    }
+    if end < start {
+        return Err(bad_request("to must not be before from".to_string()));
+    }
+
+    // counted first, so an oversized request is refused before any is built
+    let samples = td_nanos(end - start) / td_nanos(delta) + 1;
+    if samples > state.max_times as i128 {
+        return Err(bad_request(format!(
+            "Sampling {from} to {to} every {step} takes more than {} times",
+            state.max_times
+        )));
+    }
+
+    let mut times = Vec::new();
+    let mut time = Some(start);
+    while let Some(t) = time.filter(|t| *t <= end) {
+        times.push(t);
+        time = t.checked_add_signed(delta);
+    }
+    Ok(times)
+}
+
+/// One `/vars/batch` result: a response, or the sub-query's error
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BatchItem {
+    Ok(PostScheduleResponse),
+    Err { error: String },
+}
+
+/// Handler evaluating several independent `POST /vars` queries; results are
+/// returned in order and a failing sub-query doesn't fail the others
+pub async fn post_vars_batch(
+    State(state): State<AppState>,
+    format: ResponseFormat,
+    Json(queries): Json<Vec<Value>>,
+) -> JsonResponse<Vec<BatchItem>> {
+    let items = queries
+        .into_iter()
+        .map(|query| {
+            let result = serde_json::from_value(query)
+                .map_err(|e| bad_request(format!("Invalid query: {e}")))
+                .and_then(|query| evaluate_query(&state, query));
+            match result {
+                Ok(response) => BatchItem::Ok(response),
+                Err(error) => BatchItem::Err {
+                    error: error.message(),
+                },
+            }
+        })
+        .collect();
+    JsonResponse::new(&state, items).with_format(format)
+}
+
+/// Response structure for clock sanity endpoint
+#[derive(Serialize)]
+pub struct ClockResponse {
+    now: DateTime<Utc>,
+    start_date: DateTime<Utc>,
+    tolerance_seconds: i64,
+    /// false if the system clock is further before the start date than the tolerance allows
+    plausible: bool,
+}
+
+/// Handler to flag likely clock skew relative to the experiment start date
+pub async fn get_clock(State(state): State<AppState>) -> Response {
+    let Some(tolerance) = state.clock_tolerance else {
+        return not_found("Clock check is not enabled".to_string()).into_response();
+    };
+
+    let now = state.clock.now();
+    let start_date = state.loaded().start_date;
+    let plausible = now >= start_date - tolerance;
+    let status = if plausible {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = ClockResponse {
+        now,
+        start_date,
+        tolerance_seconds: tolerance.num_seconds(),
+        plausible,
+    };
+    (status, JsonResponse::new(&state, body)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct NextChangeParams {
+    /// UTC ISO‑8601 timestamp or epoch seconds to search from, defaults to now
+    time: Option<String>,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    include_admin: bool,
+}
+
+#[derive(Serialize)]
+pub struct NextChange {
+    /// null if the value never changes
+    next_change: Option<DateTime<Utc>>,
+    /// value from `next_change` on; the current value if it never changes
+    next_value: Value,
+}
+
+/// Handler reporting when each variable next changes value, and to what
+pub async fn get_next_change(
+    State(state): State<AppState>,
+    Query(params): Query<NextChangeParams>,
+) -> Result<JsonResponse<HashMap<String, NextChange>>, ApiError> {
+    let tz = resolve_tz(params.tz.as_deref())?;
+    let time = match &params.time {
+        Some(t) => state.snap_supplied(parse_query_time(t, &tz)?),
+        None => state.now(),
+    };
+    let loaded = state.loaded();
+    let namespace = params.namespace.as_deref();
+    let schedules = resolve_namespace(&state, &loaded, namespace)?;
+    let schedules = visible_schedules(&state, &loaded, namespace, schedules, params.include_admin);
+
+    let changes = schedules
+        .into_iter()
+        .map(|(var, schedule)| {
+            let next_change = schedule.next_change(&time);
+            let next_value = schedule.floor_search(&next_change.unwrap_or(time));
+            (
+                var.clone(),
+                NextChange {
+                    next_change,
+                    next_value,
+                },
+            )
+        })
+        .collect();
+    Ok(JsonResponse::new(&state, changes))
+}
+
+#[derive(Deserialize)]
+pub struct PreviewParams {
+    /// Number of evenly spaced samples across one period
+    #[serde(default = "default_preview_points")]
+    points: usize,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+}
+
+fn default_preview_points() -> usize {
+    24
+}
+
+/// Response structure for preview endpoint
+#[derive(Serialize)]
+pub struct PreviewResponse {
+    /// null for schedules that don't repeat
+    period_hours: Option<f64>,
+    /// `(offset_hours, value)` pairs measured from the schedule's start point
+    points: Vec<(f64, Value)>,
+}
+
+/// Handler sampling one period of a variable at evenly spaced offsets
+pub async fn get_preview(
+    State(state): State<AppState>,
+    Path(var): Path<String>,
+    Query(params): Query<PreviewParams>,
+) -> Result<JsonResponse<PreviewResponse>, ApiError> {
+    let loaded = state.loaded();
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let schedule = schedules
+        .get(&var)
+        .ok_or_else(|| not_found(format!("Unknown variable: '{var}'")))?;
+
+    if params.points == 0 || params.points > state.max_times {
+        return Err(bad_request(format!(
+            "points must be between 1 and {}",
+            state.max_times
+        )));
+    }
+
+    let response = match schedule.cycle() {
+        Some((start_point, period)) => {
+            let step = period / params.points as i32;
+            let points = (0..params.points)
+                .map(|i| {
+                    let offset = step * i as i32;
+                    let hours = offset.num_milliseconds() as f64 / 3.6e6;
+                    (hours, schedule.floor_search(&(start_point + offset)))
+                })
+                .collect();
+            PreviewResponse {
+                period_hours: Some(period.num_milliseconds() as f64 / 3.6e6),
+                points,
+            }
+        }
+        None => PreviewResponse {
+            period_hours: None,
+            points: vec![(0.0, schedule.floor_search(&state.now()))],
+        },
+    };
+
+    Ok(JsonResponse::new(&state, response))
+}
+
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    /// UTC ISO‑8601 timestamp of the first sample
+    start: String,
+    /// UTC ISO‑8601 timestamp; the last sample is at or before it
+    end: String,
+    /// ISO 8601 duration between samples
+    step: String,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+    /// Timezone of response timestamps: "utc" (default), "local", or an offset like "-05:00"
+    out_tz: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryPoint {
+    #[serde(serialize_with = "serialize_time")]
+    time: DateTime<FixedOffset>,
+    value: Value,
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    points: Vec<HistoryPoint>,
+}
+
+/// Shortest `/history` step; finer steps would only repeat values
+const MIN_HISTORY_STEP: TimeDelta = TimeDelta::milliseconds(1);
+
+/// Handler reconstructing a variable's values at regular steps over a past range
+pub async fn get_history(
+    State(state): State<AppState>,
+    Path(var): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> Result<JsonResponse<HistoryResponse>, ApiError> {
+    let loaded = state.loaded();
+    let out_tz = resolve_out_tz(&loaded, params.out_tz.as_deref())?;
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let schedule = schedules
+        .get(&var)
+        .ok_or_else(|| not_found(format!("Unknown variable: '{var}'")))?;
+
+    let tz = resolve_tz(params.tz.as_deref())?;
+    let start = parse_query_time(&params.start, &tz)?;
+    let end = parse_query_time(&params.end, &tz)?;
+    let step = parse_duration_iso8601(&params.step)?;
+    if end < start {
+        return Err(bad_request("end must not be before start".to_string()));
+    }
+    if step < MIN_HISTORY_STEP {
+        return Err(bad_request("step must be at least 1ms".to_string()));
+    }
+
+    let samples = td_nanos(end - start) / td_nanos(step) + 1;
+    if samples > state.max_times as i128 {
+        return Err(bad_request(format!(
+            "Requested {} samples; at most {} are allowed",
+            samples, state.max_times
+        )));
+    }
+
+    let mut points = Vec::new();
+    let mut time = start;
+    while time <= end {
+        points.push(HistoryPoint {
+            time: time.with_timezone(&out_tz),
+            value: schedule.floor_search(&time),
+        });
+        time += step;
+    }
+
+    Ok(JsonResponse::new(&state, HistoryResponse { points }))
+}
+
+/// Body of `POST /range`
+#[derive(Deserialize)]
+pub struct RangeQuery {
+    /// UTC ISO‑8601 timestamp starting the range (inclusive)
+    from: String,
+    /// UTC ISO‑8601 timestamp ending the range (exclusive)
+    to: String,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// Variables to include; defaults to all visible variables
+    vars: Option<Vec<String>>,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+    /// Timezone of response timestamps: "utc" (default), "local", or an offset like "-05:00"
+    out_tz: Option<String>,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    include_admin: bool,
+}
+
+/// Handler listing every value change of the requested variables in
+/// `[from, to)`, starting with the values in effect at `from`
+pub async fn post_range(
+    State(state): State<AppState>,
+    Json(payload): Json<RangeQuery>,
+) -> Result<JsonResponse<HashMap<String, Vec<HistoryPoint>>>, ApiError> {
+    let loaded = state.loaded();
+    let out_tz = resolve_out_tz(&loaded, payload.out_tz.as_deref())?;
+    let tz = resolve_tz(payload.tz.as_deref())?;
+    let from = parse_query_time(&payload.from, &tz)?;
+    let to = parse_query_time(&payload.to, &tz)?;
+    if to <= from {
+        return Err(bad_request("to must be after from".to_string()));
+    }
+
+    let namespace = payload.namespace.as_deref();
+    let schedules = resolve_namespace(&state, &loaded, namespace)?;
+    let selected: Vec<(&String, &Schedule)> = match &payload.vars {
+        Some(vars) => vars
+            .iter()
+            .map(|var| {
+                schedules
+                    .get_key_value(var)
+                    .ok_or_else(|| not_found(format!("Unknown variable: '{var}'")))
+            })
+            .collect::<Result<_, _>>()?,
+        None => visible_schedules(&state, &loaded, namespace, schedules, payload.include_admin),
+    };
+
+    let mut ranges = HashMap::new();
+    for (var, schedule) in selected {
+        let changes = value_changes(schedule, &from, &to, state.max_times).ok_or_else(|| {
+            bad_request(format!(
+                "Range of '{var}' spans more than {} value changes",
+                state.max_times
+            ))
+        })?;
+        let points = changes
+            .into_iter()
+            .map(|(time, value)| HistoryPoint {
+                time: time.with_timezone(&out_tz),
+                value,
+            })
+            .collect();
+        ranges.insert(var.clone(), points);
+    }
+    Ok(JsonResponse::new(&state, ranges))
+}
+
+/// Aggregation applied over a `/window`
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowOp {
+    /// time-weighted mean
+    Mean,
+    Min,
+    Max,
+    /// value at the end of the window
+    Last,
+}
+
+#[derive(Deserialize)]
+pub struct WindowParams {
+    var: String,
+    /// UTC ISO‑8601 timestamp or epoch seconds ending the window, defaults to now
+    time: Option<String>,
+    /// Timezone of query times without an offset: "utc" (default), hours like
+    /// "-5", an offset like "-05:00", or an IANA name with the `tz` feature
+    tz: Option<String>,
+    /// ISO 8601 duration the window reaches back from `time`
+    back: String,
+    op: WindowOp,
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct WindowResponse {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    op: WindowOp,
+    value: Value,
+}
+
+/// Handler aggregating a variable over `[time - back, time]`
+pub async fn get_window(
+    State(state): State<AppState>,
+    Query(params): Query<WindowParams>,
+) -> Result<JsonResponse<WindowResponse>, ApiError> {
+    let loaded = state.loaded();
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let var = &params.var;
+    let schedule = schedules
+        .get(var)
+        .ok_or_else(|| not_found(format!("Unknown variable: '{var}'")))?;
+
+    let tz = resolve_tz(params.tz.as_deref())?;
+    let end = match &params.time {
+        Some(t) => state.snap_supplied(parse_query_time(t, &tz)?),
+        None => state.now(),
+    };
+    let back = parse_duration_iso8601(&params.back)?;
+    if back <= TimeDelta::zero() {
+        return Err(bad_request("back must be a positive duration".to_string()));
+    }
+    if params.op != WindowOp::Last && !schedule.is_numeric() {
+        return Err(bad_request(format!(
+            "Variable '{var}' is not numeric; only op=last is supported"
+        )));
+    }
+    let start = end - back;
+
+    let last = schedule.floor_search(&end);
+    let value = match params.op {
+        WindowOp::Last => last,
+        op => {
+            let segments =
+                value_segments(schedule, &start, &end, state.max_times).ok_or_else(|| {
+                    bad_request(format!(
+                        "Window spans more than {} value changes",
+                        state.max_times
+                    ))
+                })?;
+            let numbers = segments
+                .iter()
+                .map(|(duration, value)| (*duration, value.as_f64().unwrap_or_default()));
+            let last = last.as_f64().unwrap_or_default();
+            let result = match op {
+                WindowOp::Mean => {
+                    // in seconds with the fraction kept, so sub-millisecond windows work
+                    let weighted: f64 = numbers
+                        .map(|(duration, v)| v * duration.as_seconds_f64())
+                        .sum();
+                    weighted / back.as_seconds_f64()
+                }
+                WindowOp::Min => numbers.map(|(_, v)| v).fold(last, f64::min),
+                WindowOp::Max => numbers.map(|(_, v)| v).fold(last, f64::max),
+                WindowOp::Last => unreachable!(),
+            };
+            json!(result)
+        }
+    };
+
+    let response = WindowResponse {
+        start,
+        end,
+        op: params.op,
+        value,
+    };
+    Ok(JsonResponse::new(&state, response))
+}
+
+#[derive(Serialize)]
+pub struct ReloadSpecsResponse {
+    specs: usize,
+}
+
+/// Read the schedule file again; a schedule piped in on stdin is gone
+async fn reread_schedule_file(state: &AppState) -> Result<String, ApiError> {
+    if state.filename.as_os_str() == "-" {
+        return Err(bad_request(
+            "Schedule was read from stdin and cannot be reloaded".to_string(),
+        ));
+    }
+    tokio::fs::read(&state.filename)
+        .await
+        .and_then(decode_schedule_bytes)
+        .map_err(|e| ApiError::Internal(format!("Failed to read schedule file: {e}")))
+}
+
+/// Handler re-reading only the spec section of the schedule file;
+/// compiled schedules are left untouched
+pub async fn reload_specs(
+    State(state): State<AppState>,
+) -> Result<JsonResponse<ReloadSpecsResponse>, ApiError> {
+    let file_data = reread_schedule_file(&state).await?;
+    let section: SpecsSection = state
+        .format
+        .parse(&file_data)
+        .and_then(|raw| serde_json::from_value(raw).map_err(|e| e.to_string()))
+        .map_err(|e| ApiError::Internal(format!("Failed to parse specs: {e}")))?;
+    let specs = section.var_type_specs;
+
+    // every loaded variable must still have a spec for its type
+    let loaded = state.loaded();
+    let empty = HashMap::new();
+    let all_schedules = std::iter::once((&loaded.schedules, &empty)).chain(
+        loaded
+            .ext_schedules
+            .values()
+            .map(|namespace| (&namespace.schedules, &namespace.specs)),
+    );
+    for (schedules, private) in all_schedules {
+        for (name, schedule) in schedules.iter() {
+            let var_type = schedule.var_type();
+            let known = specs.contains_key(&var_type) || private.contains_key(&var_type);
+            if var_type != ADMIN_VAR_TYPE && !known {
+                return Err(bad_request(format!(
+                    "New specs are missing variable type '{var_type}' used by '{name}'"
+                )));
+            }
+        }
+    }
+
+    let count = specs.len();
+    *state.specs.write().unwrap() = specs;
+    // cached bodies embed units and the admin-hidden variables, both from specs
+    if let Some(cache) = &state.cache {
+        cache.clear();
+    }
+    state.stats.generation.fetch_add(1, Ordering::Relaxed);
+    Ok(JsonResponse::new(
+        &state,
+        ReloadSpecsResponse { specs: count },
+    ))
+}
+
+#[derive(Serialize)]
+pub struct ReloadResponse {
+    variables: usize,
+    namespaces: usize,
+}
+
+/// Handler re-reading the whole schedule file and swapping in its schedules
+/// and specs; if the file doesn't load, the old ones keep being served
+pub async fn reload(
+    State(state): State<AppState>,
+) -> Result<JsonResponse<ReloadResponse>, ApiError> {
+    let file_data = reread_schedule_file(&state).await?;
+    let invalid = |e: String| bad_request(format!("Failed to parse schedule file: {e}"));
+    let file =
+        parse_schedule_file(&file_data, state.format, state.strict_fields).map_err(invalid)?;
+    let specs = file.var_type_specs.clone();
+    let mut loaded = LoadedSchedules::new(file).map_err(|e| invalid(e.to_string()))?;
+    for NamespaceFile { name, path } in &state.namespace_files {
+        let file_data = tokio::fs::read(path)
+            .await
+            .and_then(decode_schedule_bytes)
+            .map_err(|e| ApiError::Internal(format!("Failed to read namespace '{name}': {e}")))?;
+        parse_schedule_file(&file_data, FileFormat::from_path(path), state.strict_fields)
+            .and_then(|file| loaded.add_namespace(name, file))
+            .map_err(|e| invalid(format!("namespace '{name}': {e}")))?;
+    }
+
+    if let Some(id) = &state.default_namespace {
+        if !loaded.ext_schedules.contains_key(id) {
+            return Err(invalid(format!("default namespace '{id}' is missing")));
+        }
+    }
+
+    let response = ReloadResponse {
+        variables: loaded.schedules.len(),
+        namespaces: loaded.ext_schedules.len(),
+    };
+    // specs first, so a request seeing the new schedules finds their specs
+    *state.specs.write().unwrap() = specs;
+    {
+        let _update = state.updates.lock().unwrap();
+        state.loaded.store(Arc::new(loaded));
+    }
+    if let Some(cache) = &state.cache {
+        cache.clear();
+    }
+    state.stats.generation.fetch_add(1, Ordering::Relaxed);
+    Ok(JsonResponse::new(&state, response))
+}
+
+/// Handler replacing one variable's schedule with a schedule entry given in
+/// the body, resolved as if it were in the schedule file; the change lasts
+/// until the next `/reload`
+pub async fn put_var(
+    State(state): State<AppState>,
+    Path(var): Path<String>,
+    Query(params): Query<NamespaceParams>,
+    Json(raw): Json<Value>,
+) -> Result<JsonResponse<Schedule>, ApiError> {
+    if state.strict_fields {
+        ScheduleEntry::check_fields(&raw).map_err(bad_request)?;
+    }
+    let entry = ScheduleEntry::from_json(raw)
+        .map_err(|e| bad_request(format!("Invalid schedule for '{var}': {e}")))?;
+
+    // held throughout, so concurrent updates don't overwrite each other
+    let update = state.updates.lock().unwrap();
+    let loaded = state.loaded.load();
+    let loaded = loaded.as_ref();
+    let id = effective_namespace(&state, params.namespace.as_deref());
+    let schedules = resolve_namespace(&state, loaded, id)?;
+    if !schedules.contains_key(&var) {
+        return Err(not_found(format!("Unknown variable: '{var}'")));
+    }
+    // dependents hold a copy of the schedule they were resolved from
+    if let Some((dependent, _)) = schedules
+        .iter()
+        .find(|(_, schedule)| schedule.references().contains(&var.as_str()))
+    {
+        return Err(bad_request(format!(
+            "'{var}' is referenced by '{dependent}'; change it in the schedule file and reload"
+        )));
+    }
+
+    let mut specs = state.specs.read().unwrap().clone();
+    if let Some(namespace) = id.and_then(|id| loaded.ext_schedules.get(id)) {
+        specs.extend(namespace.specs.clone());
+    }
+    let schedules = replace_schedule(&loaded.info, &specs, schedules, &var, entry)
+        .map_err(|e| bad_request(e.to_string()))?;
+    let schedule = schedules[&var].clone();
+
+    let mut ext_schedules = loaded.ext_schedules.clone();
+    let schedules = match id.and_then(|id| ext_schedules.get_mut(id)) {
+        Some(namespace) => {
+            namespace.schedules = schedules;
+            loaded.schedules.clone()
+        }
+        None => schedules,
+    };
+    state.loaded.store(Arc::new(LoadedSchedules {
+        schedules,
+        ext_schedules,
+        timezone: loaded.timezone,
+        start_date: loaded.start_date,
+        info: loaded.info.clone(),
+    }));
+    drop(update);
+
+    if let Some(cache) = &state.cache {
+        cache.clear();
+    }
+    state.stats.generation.fetch_add(1, Ordering::Relaxed);
+    Ok(JsonResponse::new(&state, schedule))
+}
+
+#[derive(Deserialize)]
+pub struct VariablesParams {
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+    /// Only list variables whose name starts with this
+    prefix: Option<String>,
+    /// Include Administrative variables, which are hidden by default
+    #[serde(default)]
+    include_admin: bool,
+}
+
+#[derive(Serialize)]
+pub struct VariableListing {
+    name: String,
+    variable_type: String,
+    schedule_kind: &'static str,
+}
+
+/// Handler listing the variables of a namespace, sorted by name
+pub async fn get_variables(
+    State(state): State<AppState>,
+    Query(params): Query<VariablesParams>,
+) -> Result<JsonResponse<Vec<VariableListing>>, ApiError> {
+    let loaded = state.loaded();
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let namespace = params.namespace.as_deref();
+    let prefix = params.prefix.as_deref().unwrap_or("");
+
+    let mut variables: Vec<VariableListing> =
+        visible_schedules(&state, &loaded, namespace, schedules, params.include_admin)
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, schedule)| VariableListing {
+                name: name.clone(),
+                variable_type: schedule.var_type(),
+                schedule_kind: schedule.kind(),
+            })
+            .collect();
+    variables.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(JsonResponse::new(&state, variables))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct NamespaceParams {
+    /// Namespace ID (defaults to global namespace)
+    namespace: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DomainResponse {
+    values: Vec<Value>,
+}
+
+/// Handler listing the distinct values a variable can take
+pub async fn get_domain(
+    State(state): State<AppState>,
+    Path(var): Path<String>,
+    Query(params): Query<NamespaceParams>,
+) -> Result<JsonResponse<DomainResponse>, ApiError> {
+    let loaded = state.loaded();
+    let schedules = resolve_namespace(&state, &loaded, params.namespace.as_deref())?;
+    let schedule = schedules
+        .get(&var)
+        .ok_or_else(|| not_found(format!("Unknown variable: '{var}'")))?;
+
+    // categorical types are bounded by their declared categories
+    let categories = find_spec(
+        &state,
+        &loaded,
+        params.namespace.as_deref(),
+        &schedule.var_type(),
+    )
+    .filter(|spec| matches!(spec.var_type, VarDataType::Nominal | VarDataType::Ordinal))
+    .and_then(|spec| spec.categories);
+
+    let values = match categories {
+        Some(categories) => categories.into_iter().map(Value::String).collect(),
+        None => schedule
+            .domain()
+            .ok_or_else(|| bad_request(format!("Variable '{var}' has an unbounded domain")))?,
+    };
+
+    Ok(JsonResponse::new(&state, DomainResponse { values }))
+}
+
+/// Middleware rejecting requests without `Authorization: Bearer <admin token>`
+pub async fn require_admin_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match (&state.admin_token, provided) {
+        (Some(expected), Some(provided)) if expected == provided => next.run(request).await,
+        _ => ApiError::Unauthorized("Missing or invalid admin token".to_string()).into_response(),
+    }
+}
+
+/// Middleware rejecting requests without the API key, given either as
+/// `Authorization: Bearer <key>` or as `X-API-Key: <key>`
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let api_key = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+
+    match &state.api_key {
+        Some(expected) if [bearer, api_key].contains(&Some(expected.as_str())) => {
+            next.run(request).await
+        }
+        _ => ApiError::Unauthorized("Missing or invalid API key".to_string()).into_response(),
+    }
+}
+
+/// Middleware answering 429 with a `Retry-After` once a client IP runs out
+/// of `--rate-limit` tokens
+pub async fn limit_rate(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    match limiter.check(peer.ip()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let message = "Rate limit exceeded".to_string();
+            let mut response = ApiError::TooManyRequests(message).into_response();
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+            response
+        }
+    }
+}
+
+/// Middleware counting served requests and error responses for `/stats`,
+/// and recording them per route for `/metrics`
+pub async fn count_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // the route pattern rather than the path, to keep label cardinality bounded
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    state.stats.requests.fetch_add(1, Ordering::Relaxed);
+    if response.status().is_client_error() || response.status().is_server_error() {
+        state.stats.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    let status = response.status().as_u16();
+    state.metrics.observe(&route, status, started.elapsed());
+    if let Some(labels) = response.extensions().get::<QueryLabels>() {
+        state
+            .metrics
+            .observe_query(&labels.namespace, labels.variables);
+    }
+    response
+}
+
+/// Handler serving request and schedule metrics in the Prometheus text format
+pub async fn get_metrics(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let loaded = state.loaded();
+    let metrics = &state.metrics;
+    metrics.variables.set(loaded.schedules.len() as i64);
+    metrics.namespaces.set(loaded.ext_schedules.len() as i64);
+    let body = metrics.render().map_err(ApiError::Internal)?;
+    Ok(([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], body).into_response())
+}
+
+#[derive(Serialize)]
+pub struct StatsResponse {
+    uptime_seconds: u64,
+    requests: u64,
+    errors: u64,
+    generation: u64,
+}
+
+/// Handler returning uptime and request counters
+pub async fn get_stats(State(state): State<AppState>) -> JsonResponse<StatsResponse> {
+    let stats = &state.stats;
+    let body = StatsResponse {
+        uptime_seconds: stats.started.elapsed().as_secs(),
+        requests: stats.requests.load(Ordering::Relaxed),
+        errors: stats.errors.load(Ordering::Relaxed),
+        generation: stats.generation.load(Ordering::Relaxed),
     };
+    JsonResponse::new(&state, body)
+}
+
+/// Handler returning the effective server configuration
+pub async fn get_config(State(state): State<AppState>) -> JsonResponse<Value> {
+    JsonResponse::new(&state, state.config.as_ref().clone())
+}
+
+/// Entry in the route index served by `/api`
+#[derive(Serialize)]
+pub struct RouteDoc {
+    method: &'static str,
+    path: &'static str,
+    description: &'static str,
+    params: &'static [&'static str],
+}
+
+const ROUTES: &[RouteDoc] = &[
+    RouteDoc {
+        method: "GET",
+        path: "/",
+        description: "Values of all variables at a time",
+        params: &[
+            "time",
+            "tz",
+            "var_type",
+            "types",
+            "namespace",
+            "out_tz",
+            "include_admin",
+            "scale",
+            "offset",
+            "mode",
+            "format",
+        ],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/vars",
+        description: "Same as GET /",
+        params: &[
+            "time",
+            "tz",
+            "var_type",
+            "types",
+            "namespace",
+            "out_tz",
+            "include_admin",
+            "scale",
+            "offset",
+            "mode",
+            "format",
+        ],
+    },
+    RouteDoc {
+        method: "POST",
+        path: "/vars",
+        description: "Values of selected variables at one or more times (JSON body)",
+        params: &[
+            "time",
+            "times",
+            "from",
+            "to",
+            "step",
+            "tz",
+            "vars",
+            "namespace",
+            "out_tz",
+            "include_admin",
+            "scale",
+            "offset",
+            "mode",
+            "include_types",
+            "format",
+        ],
+    },
+    RouteDoc {
+        method: "POST",
+        path: "/vars/batch",
+        description:
+            "Several independent POST /vars queries (JSON array); errors are reported per item",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/info",
+        description: "Experiment metadata from the schedule file's Info section",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/variables",
+        description: "Variable names with their types and schedule kinds",
+        params: &["namespace", "prefix", "include_admin"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/specs",
+        description: "Variable type specs",
+        params: &["namespace"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/specs/{var_type}",
+        description: "Spec of one variable type",
+        params: &["namespace"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/export",
+        description: "Compiled schedules with resolved start points, periods and defaults",
+        params: &["namespace"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/snapshot.csv",
+        description: "Values of all variables at a time as `variable,value` CSV",
+        params: &["time", "tz", "namespace", "include_admin"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/preview/{var}",
+        description: "A variable sampled at evenly spaced offsets across one period",
+        params: &["points", "namespace"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/history/{var}",
+        description: "A variable's values at regular steps between two times",
+        params: &["start", "end", "step", "tz", "namespace", "out_tz"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/window",
+        description: "Mean, min, max or last value of a variable over a trailing window",
+        params: &["var", "time", "tz", "back", "op", "namespace"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/domain/{var}",
+        description: "Distinct values a variable can take",
+        params: &["namespace"],
+    },
+    RouteDoc {
+        method: "POST",
+        path: "/reload",
+        description: "Re-read the schedule file; on failure the old schedules stay loaded (requires the admin token)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "PUT",
+        path: "/vars/{name}",
+        description: "Replace one variable's schedule with the schedule entry in the body, until the next reload (requires the admin token)",
+        params: &["namespace"],
+    },
+    RouteDoc {
+        method: "POST",
+        path: "/reload/specs",
+        description: "Re-read the variable type specs from the schedule file (requires the admin token)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/openapi.json",
+        description: "OpenAPI document of the main routes (with the `openapi` feature)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/docs",
+        description: "Swagger UI for /openapi.json (with the `openapi` feature)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/clock",
+        description: "System clock sanity check (enabled by --clock-tolerance)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "POST",
+        path: "/range",
+        description:
+            "Every value change of variables in [from, to), starting with the values at from",
+        params: &[
+            "from",
+            "to",
+            "tz",
+            "vars",
+            "namespace",
+            "out_tz",
+            "include_admin",
+        ],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/next_change",
+        description: "When each variable next changes value, and the value it changes to",
+        params: &["time", "tz", "namespace", "include_admin"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/stream",
+        description: "Server-Sent Events with all values, sent whenever one changes",
+        params: &["namespace", "interval", "include_admin"],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/health",
+        description: "Readiness probe with the number of loaded variables and namespaces",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/metrics",
+        description: "Prometheus metrics: requests, errors and latency by route, loaded variables",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/stats",
+        description: "Uptime, request and error counts, and reload generation",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/admin/config",
+        description: "Effective server configuration (requires the admin token)",
+        params: &[],
+    },
+    RouteDoc {
+        method: "GET",
+        path: "/api",
+        description: "This route index",
+        params: &[],
+    },
+];
+
+/// Handler returning the index of available routes
+pub async fn get_api(State(state): State<AppState>) -> JsonResponse<&'static [RouteDoc]> {
+    JsonResponse::new(&state, ROUTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::FixedClock, lunaluz_deserialization::parse_schedule_file};
+    use axum::{
+        body::{to_bytes, Body},
+        extract::{connect_info::MockConnectInfo, DefaultBodyLimit},
+        http::Request,
+        middleware,
+        routing::{get, post, put},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    /// `led` is 100 from 06:00 to 18:00 UTC and 0 otherwise
+    const FILE: &str = r#"{
+        "Info": {
+            "Version": "1.0", "Timezone": 0, "StartDate": "2024-01-01T00:00:00Z",
+            "StartOffset": "PT0H", "ExperimentName": "Demo", "CabinetID": "C1",
+            "User": "tester", "Description": "sample",
+            "Parents": {"Primary": "none", "Secondary": []}
+        },
+        "VarTypeSpecs": {
+            "intensity": {"VariableType": "Ratio", "DefaultValue": 0, "Description": "light"}
+        },
+        "VariableSchedules": {
+            "led": {"VariableType": "intensity", "Period": 24, "Times": [6, 18], "Values": [100, 0]},
+            "lamp": {"VariableType": "intensity", "Value": 5}
+        }
+    }"#;
+
+    /// Write `contents` to a schedule file of its own in the temp directory
+    fn schedule_file(name: &str, contents: &str) -> PathBuf {
+        let file = format!("luz_optimon_{}_{name}.json", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// A clock the test moves by hand
+    struct ManualClock(Mutex<DateTime<Utc>>);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    /// State serving `path`, with "now" at noon on 2024-06-01
+    fn state(path: PathBuf) -> AppState {
+        let text = std::fs::read_to_string(&path).unwrap();
+        let file = parse_schedule_file(&text, FileFormat::Json, false).unwrap();
+        let specs = file.var_type_specs.clone();
+        let loaded = LoadedSchedules::new(file).unwrap();
+        AppState {
+            filename: path,
+            format: FileFormat::Json,
+            namespace_files: Vec::new(),
+            specs: Arc::new(RwLock::new(specs)),
+            loaded: Arc::new(ArcSwap::from_pointee(loaded)),
+            updates: Arc::new(Mutex::new(())),
+            strict_fields: false,
+            clock_tolerance: None,
+            plain_numbers: false,
+            max_times: 1000,
+            admin_token: None,
+            api_key: None,
+            rate_limiter: None,
+            config: Arc::new(Value::Null),
+            frozen: None,
+            cache: None,
+            time_grid: None,
+            snap_all_times: false,
+            stats: Arc::new(ServerStats::new()),
+            metrics: Arc::new(Metrics::new()),
+            shutdown: watch::channel(false).1,
+            default_namespace: None,
+            clock: Arc::new(FixedClock(
+                parse_datetime_iso8601("2024-06-01T12:00:00Z").unwrap(),
+            )),
+        }
+    }
+
+    fn app(state: AppState) -> Router {
+        Router::new()
+            .route("/", get(get_vars))
+            .route("/specs/{var_type}", get(get_spec))
+            .route("/vars", post(post_vars))
+            .route("/vars/{name}", put(put_var))
+            .route("/reload", post(reload))
+            .route("/reload/specs", post(reload_specs))
+            .route("/range", post(post_range))
+            .route("/window", get(get_window))
+            .route("/stream", get(get_stream))
+            .with_state(state)
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        let response = app.clone().oneshot(request.unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        // plain-text rejections from axum's extractors come back as a string
+        let body = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::from(String::from_utf8_lossy(&body)));
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn bad_parameters_are_400() {
+        let app = app(state(schedule_file("bad_request", FILE)));
+        let (status, body) = send(&app, "GET", "/?time=yesterday", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("Error parsing time"));
+    }
+
+    #[tokio::test]
+    async fn unknown_resources_are_404() {
+        let app = app(state(schedule_file("not_found", FILE)));
+        let (status, body) = send(&app, "GET", "/specs/missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Unknown variable type: 'missing'");
+
+        let (status, _) = send(&app, "GET", "/?namespace=missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn server_failures_are_500() {
+        let path = schedule_file("internal", FILE);
+        let app = app(state(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        let (status, body) = send(&app, "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to read schedule file"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn queries_during_reloads_see_one_snapshot() {
+        let path = schedule_file("concurrent", FILE);
+        let app = app(state(path.clone()));
+        let changed = FILE
+            .replace("[100, 0]", "[200, 0]")
+            .replace("\"Value\": 5", "\"Value\": 7");
+        let versions = [FILE.to_string(), changed];
+
+        let reloads = {
+            let app = app.clone();
+            tokio::spawn(async move {
+                for i in 0..50 {
+                    // swapped in by rename, so a reload never reads a partial file
+                    let next = path.with_extension("next");
+                    std::fs::write(&next, &versions[i % 2]).unwrap();
+                    std::fs::rename(&next, &path).unwrap();
+                    let (status, _) = send(&app, "POST", "/reload", None).await;
+                    assert_eq!(status, StatusCode::OK);
+                }
+            })
+        };
+        let queries: Vec<_> = (0..4)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        let (status, body) = send(&app, "GET", "/", None).await;
+                        assert_eq!(status, StatusCode::OK);
+                        let pair = (
+                            body["values"]["led"].clone(),
+                            body["values"]["lamp"].clone(),
+                        );
+                        assert!(
+                            pair == (json!(100), json!(5)) || pair == (json!(200), json!(7)),
+                            "mixed snapshot: {pair:?}"
+                        );
+                    }
+                })
+            })
+            .collect();
+
+        reloads.await.unwrap();
+        for query in queries {
+            query.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_serves_the_new_file() {
+        let path = schedule_file("reload", FILE);
+        let app = app(state(path.clone()));
+        std::fs::write(&path, FILE.replace("\"Value\": 5", "\"Value\": 7")).unwrap();
+
+        let (status, body) = send(&app, "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["variables"], 2);
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["lamp"], 7);
+    }
+
+    #[tokio::test]
+    async fn failed_reload_keeps_the_old_schedules() {
+        let path = schedule_file("reload_broken", FILE);
+        let app = app(state(path.clone()));
+        std::fs::write(&path, FILE.replace("[6, 18]", "[18, 6]")).unwrap();
+
+        let (status, body) = send(&app, "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to parse schedule file"));
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["led"], 100);
+        assert_eq!(body["values"]["lamp"], 5);
+    }
+
+    #[tokio::test]
+    async fn put_var_updates_a_constant() {
+        let app = app(state(schedule_file("put", FILE)));
+        let entry = json!({"VariableType": "intensity", "Value": 9});
+        let (status, body) = send(&app, "PUT", "/vars/lamp", Some(entry)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["kind"], "constant");
 
-    Ok(Json(replies))
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["lamp"], 9);
+        assert_eq!(body["values"]["led"], 100);
+    }
+
+    #[tokio::test]
+    async fn malformed_put_leaves_other_variables_alone() {
+        let app = app(state(schedule_file("put_malformed", FILE)));
+        let entry = json!({"VariableType": "intensity", "Period": 24, "Times": [6]});
+        let (status, body) = send(&app, "PUT", "/vars/led", Some(entry)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("'led'"));
+
+        let entry = json!({"VariableType": "missing", "Value": 1});
+        let (status, _) = send(&app, "PUT", "/vars/lamp", Some(entry)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["led"], 100);
+        assert_eq!(body["values"]["lamp"], 5);
+    }
+
+    #[tokio::test]
+    async fn put_var_behind_the_admin_token() {
+        let state = AppState {
+            admin_token: Some("secret".to_string()),
+            ..state(schedule_file("put_admin", FILE))
+        };
+        let app = Router::new()
+            .route("/vars/{name}", put(put_var))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_token,
+            ))
+            .with_state(state);
+        let entry = json!({"VariableType": "intensity", "Value": 9});
+        let request = |token: &str| {
+            Request::put("/vars/lamp")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::from(entry.to_string()))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(request("guess")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.oneshot(request("secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn api_key_guards_everything_but_health() {
+        let state = AppState {
+            api_key: Some("key".to_string()),
+            ..state(schedule_file("api_key", FILE))
+        };
+        // as in main, /health is added after the API key layer
+        let app = Router::new()
+            .route("/", get(get_vars))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_api_key,
+            ))
+            .route("/health", get(get_health))
+            .with_state(state);
+        let status = |uri: &str, header: Option<(&str, &str)>| {
+            let mut request = Request::get(uri);
+            if let Some((name, value)) = header {
+                request = request.header(name, value);
+            }
+            let app = app.clone();
+            async move {
+                app.oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+
+        let bearer = Some(("authorization", "Bearer key"));
+        assert_eq!(status("/", bearer).await, StatusCode::OK);
+        assert_eq!(
+            status("/", Some(("x-api-key", "key"))).await,
+            StatusCode::OK
+        );
+        let wrong = Some(("authorization", "Bearer guess"));
+        assert_eq!(status("/", wrong).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_answers_429_except_on_health() {
+        let state = AppState {
+            rate_limiter: Some(Arc::new(RateLimiter::new(0.01, 2))),
+            ..state(schedule_file("rate_limit", FILE))
+        };
+        let app = Router::new()
+            .route("/", get(get_vars))
+            .route_layer(middleware::from_fn_with_state(state.clone(), limit_rate))
+            .route("/health", get(get_health))
+            .with_state(state)
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        let get = |uri: &str| {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+
+        // a burst of two, and then one token per 100 seconds
+        for _ in 0..2 {
+            assert_eq!(get("/").await.unwrap().status(), StatusCode::OK);
+        }
+        let response = get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=100).contains(&retry_after), "{retry_after}");
+
+        for _ in 0..5 {
+            assert_eq!(get("/health").await.unwrap().status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn out_tz_serializes_times_in_the_requested_offset() {
+        let file = FILE.replace("\"Timezone\": 0", "\"Timezone\": 9");
+        let app = app(state(schedule_file("out_tz", &file)));
+        let cases = [
+            ("-05:00", "2024-06-01T07:00:00-05:00"),
+            ("%2B05:30", "2024-06-01T17:30:00+05:30"),
+            ("local", "2024-06-01T21:00:00+09:00"),
+            ("UTC", "2024-06-01T12:00:00Z"),
+        ];
+        for (out_tz, expected) in cases {
+            let (status, body) = send(&app, "GET", &format!("/?out_tz={out_tz}"), None).await;
+            assert_eq!(status, StatusCode::OK, "{out_tz}");
+            assert_eq!(body["time"], expected, "{out_tz}");
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_out_tz_is_400() {
+        let app = app(state(schedule_file("out_tz_bad", FILE)));
+        let (status, body) = send(&app, "GET", "/?out_tz=-25:00", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("'-25:00'"));
+    }
+
+    #[tokio::test]
+    async fn multi_time_queries_match_single_time_headers_and_units() {
+        let file = FILE.replace(
+            "\"Description\": \"light\"}",
+            "\"Description\": \"light\", \"Unit\": \"lux\"}",
+        );
+        let app = app(state(schedule_file("multi_time", &file)));
+        let times = "time=2023-12-31T12:00:00Z&time=2024-01-01T12:00:00Z";
+
+        let request = Request::get(format!("/?{times}&var_type=true"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // headers follow the first requested time, before the start date
+        assert_eq!(response.headers()["x-schedule-started"], "false");
+        assert_eq!(response.headers()["x-experiment-name"], "Demo");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["values"]["led"], json!([0, 100]));
+        assert_eq!(body["var_types"]["led"], "intensity");
+        assert_eq!(body["units"], json!({"led": "lux", "lamp": "lux"}));
+
+        let (status, body) = send(&app, "GET", &format!("/?{times}&types=inline"), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("types=inline"));
+    }
+
+    #[tokio::test]
+    async fn window_aggregates_a_step_schedule() {
+        let app = app(state(schedule_file("window", FILE)));
+        // led is 0 from 18:00 until 06:00, then 100
+        let window = "/window?var=led&time=2024-01-02T12:00:00Z&back=PT12H";
+        for (op, expected) in [
+            ("mean", 50.0),
+            ("min", 0.0),
+            ("max", 100.0),
+            ("last", 100.0),
+        ] {
+            let (status, body) = send(&app, "GET", &format!("{window}&op={op}"), None).await;
+            assert_eq!(status, StatusCode::OK, "{op}");
+            assert_eq!(body["value"].as_f64(), Some(expected), "{op}");
+        }
+    }
+
+    #[tokio::test]
+    async fn window_mean_over_less_than_a_millisecond() {
+        let app = app(state(schedule_file("window_sub_ms", FILE)));
+        let uri = "/window?var=led&time=2024-01-02T12:00:00Z&back=PT0.0005S&op=mean";
+        let (status, body) = send(&app, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["value"].as_f64(), Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn stream_interval_below_100ms_is_400() {
+        let app = app(state(schedule_file("stream", FILE)));
+        for interval in ["0.05", "0", "-1"] {
+            let uri = format!("/stream?interval={interval}");
+            let (status, body) = send(&app, "GET", &uri, None).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{interval}");
+            assert!(body["error"].as_str().unwrap().contains("at least 0.1"));
+        }
+        let request = Request::get("/stream?interval=0.1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_413() {
+        let app = app(state(schedule_file("body_limit", FILE))).layer(DefaultBodyLimit::max(64));
+        let query = json!({"vars": ["led"], "time": "2024-01-01T12:00:00Z"});
+        let (status, _) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let padded = json!({"vars": ["led"], "namespace": "x".repeat(64)});
+        let (status, _) = send(&app, "POST", "/vars", Some(padded)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn cache_serves_repeats_until_reload() {
+        let path = schedule_file("cache", FILE);
+        let cache = ResponseCache::new(Duration::from_secs(60), 16, TimeDelta::seconds(1));
+        let state = AppState {
+            cache: Some(Arc::new(cache)),
+            ..state(path.clone())
+        };
+        let app = app(state.clone());
+        let (_, first) = send(&app, "GET", "/", None).await;
+        assert_eq!(first["values"]["lamp"], 5);
+
+        // swapped in behind the cache's back, so only a cache hit still gives 5
+        let changed = FILE.replace("\"Value\": 5", "\"Value\": 7");
+        std::fs::write(&path, &changed).unwrap();
+        let file = parse_schedule_file(&changed, FileFormat::Json, false).unwrap();
+        state
+            .loaded
+            .store(Arc::new(LoadedSchedules::new(file).unwrap()));
+        let (_, repeat) = send(&app, "GET", "/", None).await;
+        assert_eq!(repeat, first);
+
+        let (status, _) = send(&app, "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, reloaded) = send(&app, "GET", "/", None).await;
+        assert_eq!(reloaded["values"]["lamp"], 7);
+    }
+
+    #[tokio::test]
+    async fn stream_sends_values_then_only_changes() {
+        use futures_util::StreamExt;
+
+        let clock = Arc::new(ManualClock(Mutex::new(
+            parse_datetime_iso8601("2024-06-01T05:59:59Z").unwrap(),
+        )));
+        // held, since the stream ends once the shutdown sender is gone
+        let (_shutdown, shutdown_rx) = watch::channel(false);
+        let state = AppState {
+            clock: clock.clone(),
+            shutdown: shutdown_rx,
+            ..state(schedule_file("stream_events", FILE))
+        };
+        let request = Request::get("/stream?interval=0.1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state).oneshot(request).await.unwrap();
+        let mut events = response.into_body().into_data_stream();
+        let mut next_values = async || {
+            let chunk = events.next().await.unwrap().unwrap();
+            let text = String::from_utf8(chunk.to_vec()).unwrap();
+            let data = text.trim().strip_prefix("data: ").unwrap().to_string();
+            serde_json::from_str::<Value>(&data).unwrap()["values"].clone()
+        };
+
+        assert_eq!(next_values().await, json!({"led": 0, "lamp": 5}));
+        // several ticks pass without a change, so nothing is sent
+        let quiet = tokio::time::timeout(Duration::from_millis(350), next_values()).await;
+        assert!(quiet.is_err());
+
+        *clock.0.lock().unwrap() = parse_datetime_iso8601("2024-06-01T06:00:00Z").unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(2), next_values()).await;
+        assert_eq!(changed.unwrap(), json!({"led": 100, "lamp": 5}));
+    }
+
+    #[tokio::test]
+    async fn get_vars_without_a_time_uses_the_clock() {
+        for (now, led) in [("2024-06-01T05:00:00Z", 0), ("2024-06-01T12:00:00Z", 100)] {
+            let state = AppState {
+                clock: Arc::new(FixedClock(parse_datetime_iso8601(now).unwrap())),
+                ..state(schedule_file("clock", FILE))
+            };
+            let (status, body) = send(&app(state), "GET", "/", None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["time"], now);
+            assert_eq!(body["values"]["led"], led, "{now}");
+        }
+    }
+
+    #[tokio::test]
+    async fn administrative_variables_are_hidden_by_default() {
+        let file = FILE
+            .replace(
+                r#""VarTypeSpecs": {"#,
+                r#""VarTypeSpecs": {
+                    "admin": {"VariableType": "Administrative", "DefaultValue": "",
+                              "Description": "bookkeeping"},"#,
+            )
+            .replace(
+                r#""VariableSchedules": {"#,
+                r#""VariableSchedules": {
+                    "operator": {"VariableType": "admin", "Value": "alice"},"#,
+            );
+        let app = app(state(schedule_file("admin_vars", &file)));
+
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert!(body["values"].get("operator").is_none(), "{body}");
+        assert_eq!(body["values"]["lamp"], 5);
+        let (_, body) = send(&app, "GET", "/?include_admin=true", None).await;
+        assert_eq!(body["values"]["operator"], "alice");
+
+        let (_, body) = send(&app, "POST", "/vars", Some(json!({}))).await;
+        assert!(body["values"].get("operator").is_none(), "{body}");
+        assert_eq!(body["values"]["lamp"], json!([5]));
+        let query = json!({"include_admin": true});
+        let (_, body) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(body["values"]["operator"], json!(["alice"]));
+    }
+
+    #[tokio::test]
+    async fn enumerating_past_max_times_is_400() {
+        let file = FILE.replace(
+            r#""lamp": {"#,
+            r#""blink": {"VariableType": "intensity", "Period": "PT1M", "Times": [0, 0.005],
+                         "Values": [1, 0]},
+            "lamp": {"#,
+        );
+        let app = app(state(schedule_file("max_times", &file)));
+        let years = json!({"from": "2024-01-01T00:00:00Z", "to": "2027-01-01T00:00:00Z"});
+
+        let mut sampling = years.clone();
+        sampling["step"] = json!("PT1M");
+        let (status, body) = send(&app, "POST", "/vars", Some(sampling)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("more than 1000 times"));
+
+        let mut range = years;
+        range["vars"] = json!(["blink"]);
+        let (status, body) = send(&app, "POST", "/range", Some(range)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("more than 1000 value changes"));
+    }
+
+    #[test]
+    fn plain_numbers_have_no_exponent() {
+        let body = json!({"tiny": 1e-7, "huge": 1e21, "count": 5, "whole": 2.0});
+        let text = |plain| String::from_utf8(to_json_bytes(&body, plain).unwrap()).unwrap();
+        assert!(text(false).contains("1e-7"));
+
+        let plain = text(true);
+        assert!(!plain.contains("e-") && !plain.contains("e21"), "{plain}");
+        assert!(plain.contains(r#""tiny":0.0000001"#), "{plain}");
+        assert!(
+            plain.contains(r#""huge":1000000000000000000000.0"#),
+            "{plain}"
+        );
+        // integers stay integers and whole floats stay floats
+        assert!(plain.contains(r#""count":5,"#), "{plain}");
+        assert!(plain.contains(r#""whole":2.0"#), "{plain}");
+        let parsed: Value = serde_json::from_str(&plain).unwrap();
+        assert_eq!(parsed["tiny"].as_f64(), Some(1e-7));
+    }
+
+    #[tokio::test]
+    async fn inline_types_match_the_parallel_maps() {
+        let app = app(state(schedule_file("inline_types", FILE)));
+        let (_, maps) = send(&app, "GET", "/?var_type=true", None).await;
+        let (status, inline) = send(&app, "GET", "/?types=inline", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(inline.get("var_types").is_none(), "{inline}");
+
+        let values = maps["values"].as_object().unwrap();
+        assert_eq!(inline["values"].as_object().unwrap().len(), values.len());
+        for (var, value) in values {
+            let entry = json!({"value": value, "type": maps["var_types"][var]});
+            assert_eq!(inline["values"][var], entry, "{var}");
+        }
+    }
+
+    #[tokio::test]
+    async fn specs_reload_leaves_values_alone() {
+        let path = schedule_file("reload_specs", FILE);
+        let app = app(state(path.clone()));
+        let changed = FILE
+            .replace(
+                r#""Description": "light""#,
+                r#""Description": "LED output""#,
+            )
+            .replace("\"Value\": 5", "\"Value\": 7");
+        std::fs::write(&path, changed).unwrap();
+
+        let (status, body) = send(&app, "POST", "/reload/specs", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["specs"], 1);
+        let (_, spec) = send(&app, "GET", "/specs/intensity", None).await;
+        assert_eq!(spec["Description"], "LED output");
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["lamp"], 5);
+    }
+
+    #[tokio::test]
+    async fn specs_reload_must_cover_every_variable() {
+        let path = schedule_file("reload_specs_missing", FILE);
+        let app = app(state(path.clone()));
+        std::fs::write(
+            &path,
+            FILE.replace(r#""intensity": {"#, r#""brightness": {"#),
+        )
+        .unwrap();
+
+        let (status, body) = send(&app, "POST", "/reload/specs", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("missing variable type 'intensity'"));
+        let (status, _) = send(&app, "GET", "/specs/intensity", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn post_vars_takes_iso_and_epoch_times() {
+        let app = app(state(schedule_file("epoch_times", FILE)));
+        // 12:00 as epoch seconds, 20:00 as an ISO string and 08:00:00.5 as a float
+        let query =
+            json!({"vars": ["led"], "times": [1704110400, "2024-01-01T20:00:00Z", 1704096000.5]});
+        let (status, body) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["values"]["led"], json!([100, 0, 100]));
+        assert_eq!(body["times"][0], "2024-01-01T12:00:00Z");
+        assert_eq!(body["times"][2], "2024-01-01T08:00:00.500Z");
+
+        let query =
+            json!({"vars": ["led"], "times": ["2024-01-01T12:00:00Z", "2024-01-01T20:00:00Z"]});
+        let (_, body) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(body["values"]["led"], json!([100, 0]));
+    }
+
+    #[tokio::test]
+    async fn variables_without_a_spec_are_still_served() {
+        let state = state(schedule_file("missing_spec", FILE));
+        state.specs.write().unwrap().remove("intensity");
+        let app = app(state);
+        // admin hiding and units both look the type up
+        let (status, body) = send(&app, "GET", "/?var_type=true", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["values"]["lamp"], 5);
+        assert_eq!(body["var_types"]["led"], "intensity");
+        assert_eq!(body["units"], json!({}));
+        let (_, body) = send(&app, "POST", "/vars", Some(json!({}))).await;
+        assert_eq!(body["values"]["lamp"], json!([5]));
+    }
+
+    #[tokio::test]
+    async fn msgpack_round_trips_the_json_response() {
+        let app = app(state(schedule_file("msgpack", FILE)));
+        let body = json!({"vars": ["led", "lamp"], "times": ["2024-06-01T05:00:00Z", "2024-06-01T12:00:00Z"]});
+        let request = Request::post("/vars")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/msgpack")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/msgpack"
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: PostScheduleResponse = rmp_serde::from_slice(&bytes).unwrap();
+
+        let (_, expected) = send(&app, "POST", "/vars", Some(body)).await;
+        assert_eq!(json!(decoded.values), expected["values"]);
+        assert_eq!(decoded.times.len(), 2);
+        assert_eq!(json!(decoded.values["led"]), json!([0, 100]));
+    }
+
+    #[tokio::test]
+    async fn now_queries_within_a_grid_interval_match() {
+        let clock = Arc::new(ManualClock(Mutex::new(
+            parse_datetime_iso8601("2024-06-01T12:00:10.250Z").unwrap(),
+        )));
+        let state = AppState {
+            clock: clock.clone(),
+            time_grid: Some(TimeDelta::minutes(1)),
+            ..state(schedule_file("time_grid", FILE))
+        };
+        let app = app(state);
+        let (_, first) = send(&app, "GET", "/", None).await;
+        *clock.0.lock().unwrap() += TimeDelta::seconds(40);
+        let (_, second) = send(&app, "GET", "/", None).await;
+        assert_eq!(first, second);
+        assert_eq!(first["time"], "2024-06-01T12:00:00Z");
+
+        // supplied times are left alone without --snap-all-times
+        let (_, body) = send(&app, "GET", "/?time=2024-06-01T12:00:30Z", None).await;
+        assert_eq!(body["time"], "2024-06-01T12:00:30Z");
+    }
+
+    #[tokio::test]
+    async fn stats_count_requests_errors_and_reloads() {
+        let state = state(schedule_file("stats", FILE));
+        let app = app(state.clone())
+            .merge(
+                Router::new()
+                    .route("/stats", get(get_stats))
+                    .with_state(state.clone()),
+            )
+            .layer(middleware::from_fn_with_state(state, count_requests));
+        send(&app, "GET", "/", None).await;
+        send(&app, "GET", "/?time=yesterday", None).await;
+        send(&app, "POST", "/reload", None).await;
+        let (_, first) = send(&app, "GET", "/stats", None).await;
+        // the stats request itself is counted once it is answered
+        assert_eq!(first["requests"], 3);
+        assert_eq!(first["errors"], 1);
+        assert_eq!(first["generation"], 1);
+        let (_, second) = send(&app, "GET", "/stats", None).await;
+        assert_eq!(second["requests"], 4);
+    }
+
+    #[tokio::test]
+    async fn responses_name_their_charset() {
+        let app = app(state(schedule_file("charset", FILE)));
+        let content_type = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::get(uri).body(Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                response.headers()[header::CONTENT_TYPE].clone()
+            }
+        };
+        assert_eq!(content_type("/").await, "application/json; charset=utf-8");
+        assert_eq!(
+            content_type("/?format=csv").await,
+            "text/csv; charset=utf-8"
+        );
+        // errors too
+        assert_eq!(
+            content_type("/?time=yesterday").await,
+            "application/json; charset=utf-8"
+        );
+    }
+
+    #[tokio::test]
+    async fn namespace_types_stay_in_their_namespace() {
+        let state = state(schedule_file("namespace_specs", FILE));
+        let private = FILE.replace("intensity", "heat");
+        let main = parse_schedule_file(FILE, FileFormat::Json, false).unwrap();
+        let mut loaded = LoadedSchedules::new(main).unwrap();
+        let file = parse_schedule_file(&private, FileFormat::Json, false).unwrap();
+        loaded.add_namespace("greenhouse", file).unwrap();
+        state.loaded.store(Arc::new(loaded));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/specs", get(get_specs))
+                .with_state(state),
+        );
+
+        let (_, specs) = send(&app, "GET", "/specs?namespace=greenhouse", None).await;
+        assert_eq!(specs["heat"]["Description"], "light");
+        let (_, specs) = send(&app, "GET", "/specs", None).await;
+        assert!(specs.get("heat").is_none(), "{specs}");
+        assert!(specs.get("intensity").is_some(), "{specs}");
+
+        let (status, _) = send(&app, "GET", "/specs/heat", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, "GET", "/specs/heat?namespace=greenhouse", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_namespace_use_the_default_one() {
+        let state = AppState {
+            default_namespace: Some("greenhouse".to_string()),
+            ..state(schedule_file("default_namespace", FILE))
+        };
+        let main = parse_schedule_file(FILE, FileFormat::Json, false).unwrap();
+        let mut loaded = LoadedSchedules::new(main).unwrap();
+        let other = FILE.replace("\"Value\": 5", "\"Value\": 9");
+        let file = parse_schedule_file(&other, FileFormat::Json, false).unwrap();
+        loaded.add_namespace("greenhouse", file).unwrap();
+        state.loaded.store(Arc::new(loaded));
+        let app = app(state);
+
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["lamp"], 9);
+        let (_, body) = send(&app, "POST", "/vars", Some(json!({"vars": ["lamp"]}))).await;
+        assert_eq!(body["values"]["lamp"], json!([9]));
+    }
+
+    #[tokio::test]
+    async fn celsius_served_as_fahrenheit() {
+        let file = FILE.replace(
+            r#""lamp": {"VariableType": "intensity", "Value": 5}"#,
+            r#""lamp": {"VariableType": "intensity", "Value": 5},
+               "temp": {"VariableType": "intensity", "Value": 20, "Scale": 1.8, "Offset": 32}"#,
+        );
+        let app = app(state(schedule_file("fahrenheit", &file)));
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert_eq!(body["values"]["temp"], 68.0);
+        assert_eq!(body["values"]["lamp"], 5);
+
+        // a request's own scale and offset replace the variable's
+        let (_, body) = send(&app, "GET", "/?scale=1.8&offset=32", None).await;
+        assert_eq!(body["values"]["lamp"], 41.0);
+        assert_eq!(body["values"]["temp"], 68.0);
+        let query = json!({"vars": ["lamp"], "scale": 1.8, "offset": 32});
+        let (_, body) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(body["values"]["lamp"], json!([41.0]));
+    }
+
+    #[tokio::test]
+    async fn batch_reports_errors_per_item() {
+        let state = state(schedule_file("batch", FILE));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/vars/batch", post(post_vars_batch))
+                .with_state(state),
+        );
+        let queries = json!([
+            {"vars": ["lamp"]},
+            {"vars": ["lamp"], "namespace": "missing"},
+            {"vars": ["lamp"], "times": "noon"},
+            {"vars": ["led"], "times": ["2024-06-01T05:00:00Z"]},
+        ]);
+        let (status, body) = send(&app, "POST", "/vars/batch", Some(queries)).await;
+        assert_eq!(status, StatusCode::OK);
+        let items = body.as_array().unwrap();
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0]["values"]["lamp"], json!([5]));
+        assert_eq!(items[1]["error"], "Unknown Namespace: 'missing'");
+        assert!(items[2]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid query"));
+        assert_eq!(items[3]["values"], json!({"led": [0]}));
+    }
+
+    #[tokio::test]
+    async fn info_serves_the_experiment_metadata() {
+        let state = state(schedule_file("info", FILE));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/info", get(get_info))
+                .with_state(state),
+        );
+        let (status, info) = send(&app, "GET", "/info", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["ExperimentName"], "Demo");
+        assert_eq!(info["StartDate"], "2024-01-01T00:00:00Z");
+        assert_eq!(info["CabinetID"], "C1");
+        assert_eq!(info["Parents"], json!({"Primary": "none", "Secondary": []}));
+    }
+
+    #[tokio::test]
+    async fn variables_list_names_types_and_kinds() {
+        let state = state(schedule_file("variables", FILE));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/variables", get(get_variables))
+                .with_state(state),
+        );
+        let (status, body) = send(&app, "GET", "/variables", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!([
+                {"name": "lamp", "variable_type": "intensity", "schedule_kind": "constant"},
+                {"name": "led", "variable_type": "intensity", "schedule_kind": "periodic"},
+            ])
+        );
+        let (_, body) = send(&app, "GET", "/variables?prefix=le", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["name"], "led");
+        let (status, _) = send(&app, "GET", "/variables?namespace=missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn health_counts_variables_and_namespaces() {
+        let state = state(schedule_file("health", FILE));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/health", get(get_health))
+                .with_state(state),
+        );
+        let (status, body) = send(&app, "GET", "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({"status": "ok", "variables": 2, "namespaces": 0})
+        );
+    }
+
+    #[tokio::test]
+    async fn next_change_reports_when_and_to_what() {
+        let state = state(schedule_file("next_change", FILE));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/next_change", get(get_next_change))
+                .with_state(state),
+        );
+        let (_, body) = send(&app, "GET", "/next_change", None).await;
+        assert_eq!(
+            body["led"],
+            json!({"next_change": "2024-06-01T18:00:00Z", "next_value": 0})
+        );
+        assert_eq!(body["lamp"], json!({"next_change": null, "next_value": 5}));
+        let (_, body) = send(&app, "GET", "/next_change?time=2024-06-01T20:00:00Z", None).await;
+        assert_eq!(body["led"]["next_change"], "2024-06-02T06:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn range_over_two_periods_lists_every_change() {
+        let app = app(state(schedule_file("range", FILE)));
+        let window = json!({
+            "from": "2024-06-01T03:00:00Z", "to": "2024-06-03T03:00:00Z", "vars": ["led", "lamp"]
+        });
+        let (status, body) = send(&app, "POST", "/range", Some(window)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let point = |time: &str, value: i32| json!({"time": time, "value": value});
+        assert_eq!(
+            body["led"],
+            json!([
+                point("2024-06-01T03:00:00Z", 0),
+                point("2024-06-01T06:00:00Z", 100),
+                point("2024-06-01T18:00:00Z", 0),
+                point("2024-06-02T06:00:00Z", 100),
+                point("2024-06-02T18:00:00Z", 0),
+            ])
+        );
+        assert_eq!(body["lamp"], json!([point("2024-06-01T03:00:00Z", 5)]));
+    }
+
+    #[tokio::test]
+    async fn csv_has_a_time_column_and_sorted_variables() {
+        let app = app(state(schedule_file("csv", FILE)));
+        let query = json!({"times": ["2024-06-01T05:00:00Z", "2024-06-01T12:00:00Z"]});
+        let (status, body) = send(&app, "POST", "/vars?format=csv", Some(query)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "time,lamp,led\n2024-06-01T05:00:00Z,5,0\n2024-06-01T12:00:00Z,5,100\n"
+        );
+    }
+
+    #[test]
+    fn non_scalar_csv_cells_are_compact_json() {
+        let cell = csv_field(&csv_value(json!({"r": 1, "g": [2, 3]}), false));
+        assert_eq!(cell, r#""{""g"":[2,3],""r"":1}""#);
+        assert_eq!(csv_value(json!("red"), false), "red");
+    }
+
+    #[tokio::test]
+    async fn a_schedule_from_stdin_cannot_be_reloaded() {
+        let state = AppState {
+            filename: PathBuf::from("-"),
+            ..state(schedule_file("stdin", FILE))
+        };
+        let (status, body) = send(&app(state), "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"],
+            "Schedule was read from stdin and cannot be reloaded"
+        );
+    }
+
+    #[tokio::test]
+    async fn each_namespace_file_is_queried_by_name() {
+        let namespace = |name: &str, lamp: i32| NamespaceFile {
+            name: name.to_string(),
+            path: schedule_file(
+                name,
+                &FILE.replace("\"Value\": 5", &format!("\"Value\": {lamp}")),
+            ),
+        };
+        let state = AppState {
+            namespace_files: vec![namespace("north", 1), namespace("south", 2)],
+            ..state(schedule_file("namespaces", FILE))
+        };
+        let app = app(state);
+        // namespace files are loaded along with the main file
+        let (status, _) = send(&app, "POST", "/reload", None).await;
+        assert_eq!(status, StatusCode::OK);
+        for (uri, lamp) in [("/", 5), ("/?namespace=north", 1), ("/?namespace=south", 2)] {
+            let (_, body) = send(&app, "GET", uri, None).await;
+            assert_eq!(body["values"]["lamp"], lamp, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_by_route_and_status() {
+        let state = state(schedule_file("metrics", FILE));
+        let app = app(state.clone())
+            .merge(
+                Router::new()
+                    .route("/metrics", get(get_metrics))
+                    .with_state(state.clone()),
+            )
+            .layer(middleware::from_fn_with_state(state, count_requests));
+        send(&app, "GET", "/", None).await;
+        send(&app, "GET", "/", None).await;
+        send(&app, "GET", "/?time=yesterday", None).await;
+        let (status, body) = send(&app, "GET", "/metrics", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let text = body.as_str().unwrap();
+        for line in [
+            r#"luz_requests_total{route="/",status="200"} 2"#,
+            r#"luz_requests_total{route="/",status="400"} 1"#,
+            r#"luz_errors_total{route="/",status="400"} 1"#,
+            r#"luz_request_duration_seconds_count{route="/"} 3"#,
+            "luz_loaded_variables 2",
+            "luz_loaded_namespaces 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} in\n{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn naive_times_are_read_in_the_requested_timezone() {
+        let app = app(state(schedule_file("query_tz", FILE)));
+        let cases = [
+            (
+                "/?time=2024-06-01T05:30:00&tz=-5",
+                "2024-06-01T10:30:00Z",
+                100,
+            ),
+            // an explicit offset wins over tz
+            (
+                "/?time=2024-06-01T05:30:00Z&tz=-5",
+                "2024-06-01T05:30:00Z",
+                0,
+            ),
+            ("/?time=2024-06-01T05:30:00", "2024-06-01T05:30:00Z", 0),
+        ];
+        for (uri, time, led) in cases {
+            let (status, body) = send(&app, "GET", uri, None).await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            assert_eq!(body["time"], time, "{uri}");
+            assert_eq!(body["values"]["led"], led, "{uri}");
+        }
+        #[cfg(feature = "tz")]
+        {
+            let uri = "/?time=2024-06-01T05:30:00&tz=America/New_York";
+            let (_, body) = send(&app, "GET", uri, None).await;
+            assert_eq!(body["time"], "2024-06-01T09:30:00Z");
+        }
+    }
+
+    #[tokio::test]
+    async fn compressed_responses_decode_to_the_plain_ones() {
+        use std::io::Read;
+        use tower_http::compression::CompressionLayer;
+
+        let lamps: Vec<String> = (0..500)
+            .map(|i| format!(r#""lamp{i}": {{"VariableType": "intensity", "Value": {i}}}"#))
+            .collect();
+        let file = FILE.replace(
+            r#""lamp": {"VariableType": "intensity", "Value": 5}"#,
+            &lamps.join(","),
+        );
+        let app = app(state(schedule_file("compression", &file))).layer(CompressionLayer::new());
+        let get = |encoding: Option<&str>| {
+            let mut request = Request::get("/");
+            if let Some(encoding) = encoding {
+                request = request.header(header::ACCEPT_ENCODING, encoding);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let plain = get(None).await.unwrap();
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        let plain = to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+        let gzipped = get(Some("gzip")).await.unwrap();
+        assert_eq!(gzipped.headers()[header::CONTENT_ENCODING], "gzip");
+        let gzipped = to_bytes(gzipped.into_body(), usize::MAX).await.unwrap();
+        assert!(gzipped.len() < plain.len());
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&gzipped[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        // compared as JSON, since map order differs between responses
+        let json = |bytes: &[u8]| serde_json::from_slice::<Value>(bytes).unwrap();
+        assert_eq!(json(&decoded), json(&plain));
+    }
+
+    #[tokio::test]
+    async fn wildcards_expand_to_matching_variables() {
+        let file = FILE.replace(
+            r#""lamp": {"VariableType": "intensity", "Value": 5}"#,
+            r#""lamp": {"VariableType": "intensity", "Value": 5},
+               "led_red": {"VariableType": "intensity", "Value": 1},
+               "led_blue": {"VariableType": "intensity", "Value": 2}"#,
+        );
+        let app = app(state(schedule_file("wildcards", &file)));
+        let query = |vars: Value| Some(json!({"vars": vars}));
+        let names = |body: &Value| {
+            let mut names: Vec<String> = body["values"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            names.sort();
+            names
+        };
+
+        let (_, body) = send(&app, "POST", "/vars", query(json!(["led_*"]))).await;
+        assert_eq!(names(&body), ["led_blue", "led_red"]);
+        let (_, body) = send(&app, "POST", "/vars", query(json!(["lamp", "led*"]))).await;
+        assert_eq!(names(&body), ["lamp", "led", "led_blue", "led_red"]);
+
+        let (status, body) = send(&app, "POST", "/vars", query(json!(["fan_*"]))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Pattern 'fan_*' matches no variables");
+        // exact names stay strict alongside a pattern
+        let (status, _) = send(&app, "POST", "/vars", query(json!(["led_*", "fan"]))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn step_sampling_covers_the_range() {
+        let app = app(state(schedule_file("step", FILE)));
+        let sample = |to: &str, step: &str| {
+            Some(json!({"from": "2024-06-01T04:00:00Z", "to": to, "step": step, "vars": ["led"]}))
+        };
+
+        let (_, body) = send(
+            &app,
+            "POST",
+            "/vars",
+            sample("2024-06-01T08:00:00Z", "PT2H"),
+        )
+        .await;
+        assert_eq!(
+            body["times"],
+            json!([
+                "2024-06-01T04:00:00Z",
+                "2024-06-01T06:00:00Z",
+                "2024-06-01T08:00:00Z"
+            ])
+        );
+        assert_eq!(body["values"]["led"], json!([0, 100, 100]));
+        // the last sample is the last step that fits
+        let (_, body) = send(
+            &app,
+            "POST",
+            "/vars",
+            sample("2024-06-01T07:00:00Z", "PT2H"),
+        )
+        .await;
+        assert_eq!(
+            body["times"],
+            json!(["2024-06-01T04:00:00Z", "2024-06-01T06:00:00Z"])
+        );
+
+        for step in ["PT0S", "-PT1H"] {
+            let (status, _) =
+                send(&app, "POST", "/vars", sample("2024-06-01T08:00:00Z", step)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{step}");
+        }
+    }
+
+    #[tokio::test]
+    async fn export_resolves_periodic_schedules() {
+        let file = FILE.replace(r#""StartOffset": "PT0H""#, r#""StartOffset": "PT6H""#);
+        let state = state(schedule_file("export", &file));
+        let app = app(state.clone()).merge(
+            Router::new()
+                .route("/export", get(get_export))
+                .with_state(state),
+        );
+        let (status, body) = send(&app, "GET", "/export", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let led = &body["led"];
+        assert_eq!(led["kind"], "periodic");
+        // the start offset is applied to the start date
+        assert_eq!(led["start_point"], "2024-01-01T06:00:00Z");
+        assert_eq!(led["period"], json!({"fixed": 86400.0}));
+        assert_eq!(led["times"], json!([21600.0, 64800.0]));
+        assert_eq!(led["default"], 0);
+        assert_eq!(
+            body["lamp"],
+            json!({"kind": "constant", "value": 5, "var_type": "intensity"})
+        );
+        let (status, _) = send(&app, "GET", "/export?namespace=missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn spec_units_reach_specs_and_values() {
+        let file = FILE.replace(
+            "\"Description\": \"light\"}",
+            "\"Description\": \"light\", \"Unit\": \"lux\"}",
+        );
+        // files without units still load
+        let plain = app(state(schedule_file("no_units", FILE)));
+        let app = app(state(schedule_file("units", &file)));
+        let (_, spec) = send(&app, "GET", "/specs/intensity", None).await;
+        assert_eq!(spec["Unit"], "lux");
+        let (_, body) = send(&app, "GET", "/?var_type=true", None).await;
+        assert_eq!(body["units"], json!({"led": "lux", "lamp": "lux"}));
+        // only alongside the types
+        let (_, body) = send(&app, "GET", "/", None).await;
+        assert!(body.get("units").is_none(), "{body}");
+        let (_, spec) = send(&plain, "GET", "/specs/intensity", None).await;
+        assert!(spec.get("Unit").is_none_or(Value::is_null), "{spec}");
+    }
+
+    #[tokio::test]
+    async fn a_single_spec_is_served_by_name() {
+        let app = app(state(schedule_file("spec", FILE)));
+        let (status, spec) = send(&app, "GET", "/specs/intensity", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(spec["DefaultValue"], 0);
+        assert_eq!(spec["VariableType"], "Ratio");
+        // unknown types are covered by unknown_resources_are_404
+    }
+
+    #[tokio::test]
+    async fn post_vars_types_only_when_requested() {
+        let app = app(state(schedule_file("post_types", FILE)));
+        let (_, body) = send(&app, "POST", "/vars", Some(json!({"vars": ["led"]}))).await;
+        assert!(body.get("var_types").is_none(), "{body}");
+        let query = json!({"vars": ["led"], "include_types": true});
+        let (_, body) = send(&app, "POST", "/vars", Some(query)).await;
+        assert_eq!(body["var_types"], json!({"led": "intensity"}));
+    }
+
+    #[tokio::test]
+    async fn metrics_count_queries_per_namespace() {
+        let state = state(schedule_file("namespace_metrics", FILE));
+        let mut loaded =
+            LoadedSchedules::new(parse_schedule_file(FILE, FileFormat::Json, false).unwrap())
+                .unwrap();
+        let file = parse_schedule_file(FILE, FileFormat::Json, false).unwrap();
+        loaded.add_namespace("greenhouse", file).unwrap();
+        state.loaded.store(Arc::new(loaded));
+        let app = app(state.clone())
+            .merge(
+                Router::new()
+                    .route("/metrics", get(get_metrics))
+                    .with_state(state.clone()),
+            )
+            .layer(middleware::from_fn_with_state(state, count_requests));
+        send(&app, "GET", "/", None).await;
+        send(&app, "GET", "/", None).await;
+        let query = json!({"vars": ["led"], "namespace": "greenhouse"});
+        send(&app, "POST", "/vars", Some(query)).await;
+
+        let (_, body) = send(&app, "GET", "/metrics", None).await;
+        let text = body.as_str().unwrap();
+        for line in [
+            r#"luz_namespace_queries_total{namespace="global"} 2"#,
+            r#"luz_namespace_queries_total{namespace="greenhouse"} 1"#,
+            // two variables twice, then one
+            "luz_response_variables_sum 5",
+            "luz_response_variables_count 3",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} in\n{text}");
+        }
+    }
 }